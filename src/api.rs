@@ -1,19 +1,60 @@
 use chrono::NaiveDateTime;
-use failure::{Error, format_err};
+use failure::{Error, bail, format_err};
+use hmac::{Hmac, Mac};
 use serde::Serialize;
 use serde_derive::{Serialize, Deserialize};
-use serde_json;
+use serde_json::{self, Map, Value};
 
 use brdgme_game::command::Spec as CommandSpec;
 use brdgme_game::errors::GameError;
 use brdgme_game::{Gamer, Log, Status};
 use brdgme_markup;
+use json_patch::{self, Patch};
+use sha2::Sha256;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::encoding;
+use crate::suggest::Suggestion;
+
+/// The version of the request and response protocol, bumped whenever an existing variant changes
+/// shape. New optional variants are advertised through `Hello::capabilities` instead.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The largest seed generated for `Request::New`, 2^53 - 1 is the largest integer a JavaScript
+/// number holds exactly.
+pub const MAX_SEED: u64 = (1 << 53) - 1;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Request {
+    Hello,
     PlayerCounts,
+    Info,
     New {
         players: usize,
+        /// Seeds the game RNG so setup can be reproduced, a random seed is used if omitted. Seeds
+        /// above `MAX_SEED` can't be represented exactly by JavaScript clients.
+        #[serde(default)]
+        seed: Option<u64>,
+        /// A JSON object of game options matching the schema from `Request::Options`, omitted
+        /// options use their defaults.
+        #[serde(default)]
+        options: Option<Value>,
+    },
+    Options,
+    /// Rebuilds a game from its creation parameters by applying each command in order.
+    Replay {
+        players: usize,
+        /// Required for games which support seeding, as a random seed wouldn't reproduce the game.
+        #[serde(default)]
+        seed: Option<u64>,
+        #[serde(default)]
+        options: Option<Value>,
+        names: Vec<String>,
+        commands: Vec<ReplayCommand>,
     },
     Status {
         game: String,
@@ -23,16 +64,157 @@ pub enum Request {
         command: String,
         names: Vec<String>,
         game: String,
+        /// Returns `Response::PlayDiff` instead of `Response::Play`.
+        #[serde(default)]
+        diff: bool,
+    },
+    /// Runs a command against the game without returning the new state.
+    Validate {
+        player: usize,
+        command: String,
+        names: Vec<String>,
+        game: String,
+    },
+    /// Suggests the next tokens for a partially typed command.
+    Suggest {
+        player: usize,
+        partial_input: String,
+        #[serde(default)]
+        names: Vec<String>,
+        game: String,
     },
     PubRender {
         game: String,
+        #[serde(default)]
+        format: RenderFormat,
+        /// Pads each line of transformed formats to this width.
+        #[serde(default)]
+        width: Option<usize>,
+        /// Player names for transformed formats, placeholders are used for missing names.
+        #[serde(default)]
+        names: Vec<String>,
     },
     PlayerRender {
         player: usize,
         game: String,
+        #[serde(default)]
+        format: RenderFormat,
+        /// Pads each line of transformed formats to this width.
+        #[serde(default)]
+        width: Option<usize>,
+        /// Player names for transformed formats, placeholders are used for missing names.
+        #[serde(default)]
+        names: Vec<String>,
+    },
+    /// Filters stored logs down to those a player, or a spectator if `player` is `None`, may see.
+    Logs {
+        logs: Vec<CliLog>,
+        #[serde(default)]
+        player: Option<usize>,
+    },
+    /// Upgrades a game state encoded by an older version of the game to the current state version.
+    Migrate {
+        game: String,
+        /// The state version `game` was encoded with, read from the state envelope if omitted.
+        #[serde(default)]
+        from_version: Option<u32>,
+    },
+    /// Handles multiple requests in a single round trip.
+    Batch {
+        requests: Vec<Request>,
+        /// Runs each `Play` request against the state from the previous response instead of its
+        /// own `game`, stopping at the first response without a game state. The state after a
+        /// `Response::PlayDiff` is found by applying its patch.
+        #[serde(default)]
+        chain: bool,
     },
 }
 
+impl Request {
+    /// The names of all request variants, matching their serialised tags.
+    pub const NAMES: &'static [&'static str] = &[
+        "Hello",
+        "PlayerCounts",
+        "Info",
+        "New",
+        "Options",
+        "Replay",
+        "Status",
+        "Play",
+        "Validate",
+        "Suggest",
+        "PubRender",
+        "PlayerRender",
+        "Logs",
+        "Migrate",
+        "Batch",
+    ];
+
+    /// Builds a request from a variant name and its fields, unit variants have no fields. An empty
+    /// object or array also counts as no fields when `name` is a unit variant.
+    pub fn from_parts(name: &str, fields: Option<Value>) -> serde_json::Result<Request> {
+        let empty = match fields {
+            Some(Value::Object(ref map)) => map.is_empty(),
+            Some(Value::Array(ref items)) => items.is_empty(),
+            _ => false,
+        };
+        if empty {
+            if let Ok(req) = serde_json::from_value(Value::String(name.to_owned())) {
+                return Ok(req);
+            }
+        }
+        serde_json::from_value(match fields {
+            None | Some(Value::Null) => Value::String(name.to_owned()),
+            Some(fields) => {
+                let mut tagged = Map::new();
+                tagged.insert(name.to_owned(), fields);
+                Value::Object(tagged)
+            }
+        })
+    }
+
+    /// The name of the request variant, matching its serialised tag.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Request::Hello => "Hello",
+            Request::PlayerCounts => "PlayerCounts",
+            Request::Info => "Info",
+            Request::New { .. } => "New",
+            Request::Options => "Options",
+            Request::Replay { .. } => "Replay",
+            Request::Status { .. } => "Status",
+            Request::Play { .. } => "Play",
+            Request::Validate { .. } => "Validate",
+            Request::Suggest { .. } => "Suggest",
+            Request::PubRender { .. } => "PubRender",
+            Request::PlayerRender { .. } => "PlayerRender",
+            Request::Logs { .. } => "Logs",
+            Request::Migrate { .. } => "Migrate",
+            Request::Batch { .. } => "Batch",
+        }
+    }
+
+    /// The game state sent with the request, if any.
+    pub fn game_state_mut(&mut self) -> Option<&mut String> {
+        match *self {
+            Request::Status { ref mut game }
+            | Request::Play { ref mut game, .. }
+            | Request::Validate { ref mut game, .. }
+            | Request::Suggest { ref mut game, .. }
+            | Request::PubRender { ref mut game, .. }
+            | Request::PlayerRender { ref mut game, .. }
+            | Request::Migrate { ref mut game, .. } => Some(game),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayCommand {
+    pub player: usize,
+    pub command: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CliLog {
     pub content: String,
@@ -54,6 +236,19 @@ impl CliLog {
     pub fn from_logs(logs: &[Log]) -> Vec<CliLog> {
         logs.iter().map(CliLog::from_log).collect()
     }
+
+    /// Whether the log can be shown to a player, or to a spectator if `player` is `None`.
+    pub fn visible_to(&self, player: Option<usize>) -> bool {
+        self.public || player.map_or(false, |p| self.to.contains(&p))
+    }
+
+    /// The logs which can be shown to a player, or to a spectator if `player` is `None`.
+    pub fn filter_for(logs: &[CliLog], player: Option<usize>) -> Vec<CliLog> {
+        logs.iter()
+            .filter(|l| l.visible_to(player))
+            .cloned()
+            .collect()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -61,6 +256,118 @@ pub struct GameResponse {
     pub state: String,
     pub points: Vec<f32>,
     pub status: Status,
+    /// The validated options the game was created with, only included in `Response::New`.
+    #[serde(default)]
+    pub options: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameOption {
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: GameOptionKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum GameOptionKind {
+    Bool {
+        default: bool,
+    },
+    Int {
+        min: Option<i64>,
+        max: Option<i64>,
+        default: i64,
+    },
+    Enum {
+        values: Vec<String>,
+        default: String,
+    },
+}
+
+impl GameOptionKind {
+    fn default_value(&self) -> Value {
+        match *self {
+            GameOptionKind::Bool { default } => default.into(),
+            GameOptionKind::Int { default, .. } => default.into(),
+            GameOptionKind::Enum { ref default, .. } => default.clone().into(),
+        }
+    }
+
+    fn validate(&self, value: &Value) -> Result<(), String> {
+        match (self, value) {
+            (GameOptionKind::Bool { .. }, Value::Bool(_)) => Ok(()),
+            (GameOptionKind::Int { min, max, .. }, Value::Number(n)) => match n.as_i64() {
+                Some(n) if min.map_or(false, |min| n < min) => {
+                    Err(format!("must be at least {}", min.unwrap()))
+                }
+                Some(n) if max.map_or(false, |max| n > max) => {
+                    Err(format!("must be at most {}", max.unwrap()))
+                }
+                Some(_) => Ok(()),
+                None => Err("must be an integer".to_string()),
+            },
+            (GameOptionKind::Enum { values, .. }, Value::String(s)) => {
+                if values.contains(s) {
+                    Ok(())
+                } else {
+                    Err(format!("must be one of {}", values.join(", ")))
+                }
+            }
+            (GameOptionKind::Bool { .. }, _) => Err("must be a boolean".to_string()),
+            (GameOptionKind::Int { .. }, _) => Err("must be an integer".to_string()),
+            (GameOptionKind::Enum { .. }, _) => Err("must be a string".to_string()),
+        }
+    }
+}
+
+/// Validates options against a schema, returning a complete options object with defaults filled in
+/// for omitted options.
+pub fn validate_options(schema: &[GameOption], options: Option<&Value>) -> Result<Value, String> {
+    let empty = Map::new();
+    let given = match options {
+        None | Some(Value::Null) => &empty,
+        Some(Value::Object(o)) => o,
+        Some(_) => return Err("options must be an object".to_string()),
+    };
+    if let Some(unknown) = given.keys().find(|k| !schema.iter().any(|o| o.name == **k)) {
+        return Err(format!("unknown option '{}'", unknown));
+    }
+    let mut validated = Map::new();
+    for option in schema {
+        let value = match given.get(&option.name) {
+            Some(v) => {
+                option
+                    .kind
+                    .validate(v)
+                    .map_err(|e| format!("option '{}' {}", option.name, e))?;
+                v.clone()
+            }
+            None => option.kind.default_value(),
+        };
+        validated.insert(option.name.clone(), value);
+    }
+    Ok(Value::Object(validated))
+}
+
+/// The format of the `render` field in `Response::PubRender` and `Response::PlayerRender`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// Raw brdgme markup.
+    Markup,
+    /// Text with ANSI escape codes for terminals.
+    Ansi,
+    /// Text with all styling removed.
+    Plain,
+    /// HTML with player colours applied.
+    Html,
+    /// A standalone SVG image, see `render::svg`.
+    Svg,
+}
+
+impl Default for RenderFormat {
+    fn default() -> Self {
+        RenderFormat::Markup
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -76,14 +383,63 @@ pub struct PlayerRender {
     pub command_spec: Option<CommandSpec>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub protocol_version: u32,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Names of the optional `Request` variants supported by the game.
+    pub capabilities: Vec<String>,
+}
+
+impl Hello {
+    /// The handshake assumed for games built before `Request::Hello` existed.
+    pub fn legacy() -> Hello {
+        Hello {
+            protocol_version: 0,
+            name: None,
+            version: None,
+            capabilities: vec![],
+        }
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Info {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// The rules of the game in brdgme markup.
+    pub rules: Option<String>,
+    pub player_counts: Vec<usize>,
+    /// Estimated duration of a game in minutes.
+    pub duration: Option<u32>,
+    pub version: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Response {
+    Hello {
+        hello: Hello,
+    },
     PlayerCounts {
         player_counts: Vec<usize>,
     },
+    Info {
+        info: Info,
+    },
+    Options {
+        options: Vec<GameOption>,
+    },
     New {
         game: GameResponse,
         logs: Vec<CliLog>,
+        /// The seed used to create the game, if the game supports seeding.
+        #[serde(default)]
+        seed: Option<u64>,
         public_render: PubRender,
         player_renders: Vec<PlayerRender>,
     },
@@ -100,38 +456,449 @@ pub enum Response {
         public_render: PubRender,
         player_renders: Vec<PlayerRender>,
     },
+    Replay {
+        game: GameResponse,
+        /// Logs from creating the game.
+        logs: Vec<CliLog>,
+        /// Logs from each command, in the same order as the request.
+        command_logs: Vec<Vec<CliLog>>,
+        public_render: PubRender,
+        player_renders: Vec<PlayerRender>,
+    },
+    Validate {
+        /// The part of the command which was parsed.
+        parsed: String,
+        remaining_input: String,
+        logs: Vec<CliLog>,
+        can_undo: bool,
+    },
+    Suggest {
+        suggestions: Vec<Suggestion>,
+        /// Whether the partial input is already a complete command.
+        complete: bool,
+    },
+    /// The command at `index` failed during a replay.
+    ReplayError {
+        index: usize,
+        error: Box<Response>,
+    },
     PubRender {
         render: PubRender,
     },
     PlayerRender {
         render: PlayerRender,
     },
+    Logs {
+        logs: Vec<CliLog>,
+    },
+    /// A `Response::Play` with the game and renders replaced by a patch against the `Snapshot`
+    /// of the input game.
+    PlayDiff {
+        patch: Patch,
+        logs: Vec<CliLog>,
+        can_undo: bool,
+        remaining_input: String,
+    },
+    Migrate {
+        game: GameResponse,
+        public_render: PubRender,
+        player_renders: Vec<PlayerRender>,
+    },
+    Batch {
+        responses: Vec<Response>,
+    },
+    /// The game state was encoded with a state version other than the one the game uses, older
+    /// states can be upgraded using `Request::Migrate` if the game supports it.
+    StateVersionError {
+        state_version: u32,
+        current_version: u32,
+        message: String,
+    },
     UserError {
         message: String,
+        #[serde(default)]
+        kind: Option<ErrorKind>,
+        /// A stable numeric code for `kind`, for clients which localise messages.
+        #[serde(default)]
+        code: Option<u32>,
+        /// The part of the command input which caused the error.
+        #[serde(default)]
+        span: Option<Span>,
     },
     SystemError {
         message: String,
     },
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    PlayerCount,
+    InvalidInput,
+    NotYourTurn,
+    Finished,
+    Parse,
+    InvalidOptions,
+    UnexpectedInput,
+}
+
+impl ErrorKind {
+    /// Codes are part of the protocol and must never be reused for a different kind.
+    pub fn code(self) -> u32 {
+        match self {
+            ErrorKind::PlayerCount => 1,
+            ErrorKind::InvalidInput => 2,
+            ErrorKind::NotYourTurn => 3,
+            ErrorKind::Finished => 4,
+            ErrorKind::Parse => 5,
+            ErrorKind::InvalidOptions => 6,
+            ErrorKind::UnexpectedInput => 7,
+        }
+    }
+}
+
+/// A byte range in the command input, `end` is exclusive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Spans the word in `input` starting at `offset`.
+    pub fn word_at(input: &str, offset: usize) -> Span {
+        let start = offset.min(input.len());
+        let end = input
+            .get(start..)
+            .and_then(|rest| rest.find(char::is_whitespace))
+            .map_or(input.len(), |len| start + len);
+        Span { start, end }
+    }
+}
+
+/// A game and its renders, used to diff consecutive game states.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub game: GameResponse,
+    pub public_render: PubRender,
+    pub player_renders: Vec<PlayerRender>,
+}
+
+/// The JSON shape patches are applied to, embedded state strings are parsed so patches only touch
+/// the parts of the state which changed.
+#[derive(Serialize, Deserialize)]
+struct DiffView {
+    state: Value,
+    points: Vec<f32>,
+    status: Status,
+    pub_state: Value,
+    public_render: String,
+    player_renders: Vec<DiffPlayerRender>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiffPlayerRender {
+    player_state: Value,
+    render: String,
+    command_spec: Option<CommandSpec>,
+}
+
+impl Snapshot {
+    /// Creates a JSON Patch which transforms this snapshot into `to`.
+    pub fn diff(&self, to: &Snapshot) -> Result<Patch, Error> {
+        Ok(json_patch::diff(&self.diff_value()?, &to.diff_value()?))
+    }
+
+    /// Applies a patch created by `diff`, options are carried over as they aren't part of the diff.
+    /// A compressed game state is decompressed first, the patched state is left uncompressed.
+    pub fn apply(&self, patch: &Patch) -> Result<Snapshot, Error> {
+        let mut value = self.diff_value()?;
+        json_patch::patch(&mut value, patch)
+            .map_err(|e| format_err!("unable to apply patch: {}", e))?;
+        let view: DiffView = serde_json::from_value(value)?;
+        Ok(Snapshot {
+            game: GameResponse {
+                state: serde_json::to_string(&view.state)?,
+                points: view.points,
+                status: view.status,
+                options: self.game.options.clone(),
+            },
+            public_render: PubRender {
+                pub_state: serde_json::to_string(&view.pub_state)?,
+                render: view.public_render,
+            },
+            player_renders: view
+                .player_renders
+                .into_iter()
+                .map(|pr| -> Result<PlayerRender, Error> {
+                    Ok(PlayerRender {
+                        player_state: serde_json::to_string(&pr.player_state)?,
+                        render: pr.render,
+                        command_spec: pr.command_spec,
+                    })
+                })
+                .collect::<Result<Vec<PlayerRender>, Error>>()?,
+        })
+    }
+
+    fn diff_value(&self) -> Result<Value, Error> {
+        Ok(serde_json::to_value(DiffView {
+            state: serde_json::from_str(&encoding::decompress_state(&self.game.state)?)?,
+            points: self.game.points.clone(),
+            status: self.game.status.clone(),
+            pub_state: serde_json::from_str(&self.public_render.pub_state)?,
+            public_render: self.public_render.render.clone(),
+            player_renders: self
+                .player_renders
+                .iter()
+                .map(|pr| -> Result<DiffPlayerRender, Error> {
+                    Ok(DiffPlayerRender {
+                        player_state: serde_json::from_str(&pr.player_state)?,
+                        render: pr.render.clone(),
+                        command_spec: pr.command_spec.clone(),
+                    })
+                })
+                .collect::<Result<Vec<DiffPlayerRender>, Error>>()?,
+        })?)
+    }
+}
+
+/// The envelope game state is encoded in, tagging it with the version of the game's state layout.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StateEnvelope<T> {
+    pub state_version: u32,
+    pub game: T,
+}
+
+impl StateEnvelope<Value> {
+    /// Parses an encoded game state, states encoded before versioning have no envelope and are
+    /// treated as version 0.
+    pub fn parse(state: &str) -> serde_json::Result<StateEnvelope<Value>> {
+        let value: Value = serde_json::from_str(state)?;
+        Ok(match serde_json::from_value(value.clone()) {
+            Ok(envelope) => envelope,
+            Err(_) => StateEnvelope {
+                state_version: 0,
+                game: value,
+            },
+        })
+    }
+}
+
+/// The environment variable containing the key used to sign game states.
+pub const STATE_KEY_ENV: &str = "BRDGME_STATE_KEY";
+
+/// The environment variable containing the path of a file containing the key used to sign game
+/// states, used if `STATE_KEY_ENV` isn't set.
+pub const STATE_KEY_FILE_ENV: &str = "BRDGME_STATE_KEY_FILE";
+
+/// A secret key used to sign game states with HMAC-SHA256 so clients can't tamper with them.
+#[derive(Clone)]
+pub struct StateKey(Vec<u8>);
+
+impl StateKey {
+    pub fn new<K: Into<Vec<u8>>>(key: K) -> Result<StateKey, Error> {
+        let key = key.into();
+        if key.is_empty() {
+            bail!("state key must not be empty");
+        }
+        Ok(StateKey(key))
+    }
+
+    /// Reads a key from a file, ignoring trailing whitespace.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<StateKey, Error> {
+        let path = path.as_ref();
+        let mut key = fs::read(path)
+            .map_err(|e| format_err!("unable to read state key from {}: {}", path.display(), e))?;
+        while key.last().map_or(false, u8::is_ascii_whitespace) {
+            key.pop();
+        }
+        StateKey::new(key)
+    }
+
+    /// Reads a key from `STATE_KEY_ENV` or the file named by `STATE_KEY_FILE_ENV`, returning
+    /// `None` if neither is set.
+    pub fn from_env() -> Result<Option<StateKey>, Error> {
+        if let Some(key) = env::var_os(STATE_KEY_ENV) {
+            return key
+                .into_string()
+                .map_err(|_| format_err!("{} is not valid unicode", STATE_KEY_ENV))
+                .and_then(StateKey::new)
+                .map(Some);
+        }
+        match env::var_os(STATE_KEY_FILE_ENV) {
+            Some(path) => StateKey::from_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn mac(&self, data: &str) -> Hmac<Sha256> {
+        // HMAC accepts keys of any length.
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.0).expect("invalid HMAC key length");
+        mac.input(data.as_bytes());
+        mac
+    }
+
+    fn sign(&self, data: &str) -> String {
+        self.mac(data)
+            .result()
+            .code()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn verify(&self, data: &str, signature: &str) -> bool {
+        if signature.len() % 2 != 0 || !signature.is_ascii() {
+            return false;
+        }
+        let bytes = (0..signature.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&signature[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>();
+        match bytes {
+            Ok(bytes) => self.mac(data).verify(&bytes).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl fmt::Debug for StateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Never leak the key into logs.
+        f.write_str("StateKey(..)")
+    }
+}
+
+/// A signed game state, `state` is the encoded `StateEnvelope`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignedState {
+    signature: String,
+    state: String,
+}
+
+/// How game states are encoded into `GameResponse::state`.
+#[derive(Debug, Clone, Default)]
+pub struct StateFormat {
+    pub state_version: u32,
+    /// Signs states when set, unsigned or incorrectly signed states are then rejected.
+    pub key: Option<StateKey>,
+}
+
+impl StateFormat {
+    pub fn encode<T: Serialize>(&self, game: &T) -> Result<String, Error> {
+        let state = serde_json::to_string(&StateEnvelope {
+            state_version: self.state_version,
+            game,
+        }).map_err(|e| format_err!("unable to encode game state: {}", e))?;
+        Ok(match self.key {
+            Some(ref key) => serde_json::to_string(&SignedState {
+                signature: key.sign(&state),
+                state,
+            })?,
+            None => state,
+        })
+    }
+
+    /// Decodes the envelope of a game state, verifying its signature if a key is set. The state
+    /// version isn't checked.
+    pub fn decode(&self, state: &str) -> Result<StateEnvelope<Value>, Error> {
+        let verified;
+        let state = match self.key {
+            Some(ref key) => {
+                let signed: SignedState = serde_json::from_str(state)
+                    .map_err(|_| format_err!("game state is not signed"))?;
+                if !key.verify(&signed.state, &signed.signature) {
+                    bail!("game state signature mismatch");
+                }
+                verified = signed.state;
+                &verified
+            }
+            None => state,
+        };
+        StateEnvelope::parse(state).map_err(|e| format_err!("unable to decode game state: {}", e))
+    }
+}
+
 impl GameResponse {
     pub fn from_gamer<T: Gamer + Serialize>(gamer: &T) -> Result<GameResponse, Error> {
+        GameResponse::from_gamer_with(gamer, &StateFormat::default())
+    }
+
+    /// Encodes the game state using `format`, tagging it with a state version and signing it.
+    pub fn from_gamer_with<T: Gamer + Serialize>(
+        gamer: &T,
+        format: &StateFormat,
+    ) -> Result<GameResponse, Error> {
         Ok(GameResponse {
-            state: serde_json::to_string(gamer)
-                .map_err(|e| format_err!("unable to encode game state: {}", e))?,
+            state: format.encode(gamer)?,
             points: gamer.points(),
             status: gamer.status(),
+            options: None,
         })
     }
 }
 
+impl Response {
+    /// The game state contained in the response, if any.
+    pub fn game_state(&self) -> Option<&str> {
+        match *self {
+            Response::New { ref game, .. }
+            | Response::Status { ref game, .. }
+            | Response::Play { ref game, .. }
+            | Response::Replay { ref game, .. }
+            | Response::Migrate { ref game, .. } => Some(&game.state),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to the game state contained in the response, if any.
+    pub fn game_state_mut(&mut self) -> Option<&mut String> {
+        match *self {
+            Response::New { ref mut game, .. }
+            | Response::Status { ref mut game, .. }
+            | Response::Play { ref mut game, .. }
+            | Response::Replay { ref mut game, .. }
+            | Response::Migrate { ref mut game, .. } => Some(&mut game.state),
+            _ => None,
+        }
+    }
+
+    pub fn user_error<M: Into<String>>(kind: ErrorKind, message: M, span: Option<Span>) -> Self {
+        Response::UserError {
+            message: message.into(),
+            kind: Some(kind),
+            code: Some(kind.code()),
+            span,
+        }
+    }
+
+    /// Converts a game error, using `input` to locate the span of parse errors.
+    pub fn from_game_error(e: GameError, input: &str) -> Self {
+        let span = match e {
+            GameError::Parse { offset, .. } => Some(Span::word_at(input, offset)),
+            _ => None,
+        };
+        Response::from_game_error_span(e, span)
+    }
+
+    fn from_game_error_span(e: GameError, span: Option<Span>) -> Self {
+        let kind = match e {
+            GameError::Internal { message } => return Response::SystemError { message },
+            GameError::PlayerCount { .. } => ErrorKind::PlayerCount,
+            GameError::InvalidInput { .. } => ErrorKind::InvalidInput,
+            GameError::NotYourTurn => ErrorKind::NotYourTurn,
+            GameError::Finished => ErrorKind::Finished,
+            GameError::Parse { .. } => ErrorKind::Parse,
+        };
+        Response::user_error(kind, e.to_string(), span)
+    }
+}
+
 impl From<GameError> for Response {
+    /// There's no command input to locate parse errors in, so no span is given.
     fn from(e: GameError) -> Self {
-        match e {
-            GameError::Internal { message } => Response::SystemError { message },
-            e => Response::UserError {
-                message: e.to_string(),
-            },
-        }
+        Response::from_game_error_span(e, None)
     }
 }
@@ -1,19 +1,344 @@
+use failure::{bail, Error};
 use serde_json;
 
-use std::io::{Read, Write};
+use std::env;
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::io::ErrorKind;
+use std::io::{stdin, stdout, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
 
 use crate::api::{Request, Response};
+use crate::encoding::{self, Codec, Compression, Encoding};
+use crate::http;
+use crate::jsonrpc;
 use crate::requester::Requester;
+use crate::schema;
+use crate::typescript;
+
+/// The argument which tells `run` to start a persistent `serve` loop instead of handling a single
+/// request.
+pub const SERVE_ARG: &str = "--serve";
+
+/// The argument which tells `run` to listen on the TCP address given as the next argument.
+pub const TCP_ARG: &str = "--tcp";
+
+/// The argument which tells `run` to listen on the Unix socket path given as the next argument.
+pub const UNIX_ARG: &str = "--unix";
+
+/// The argument which tells `run` to start an HTTP server on the address given as the next
+/// argument.
+pub const HTTP_ARG: &str = "--http";
+
+/// The argument which tells `run` to use `Framing::JsonRpc`, it may appear anywhere in the
+/// arguments.
+pub const JSONRPC_ARG: &str = "--jsonrpc";
+
+/// The argument which tells `run` to print the JSON Schema of the protocol, see `schema`.
+pub const SCHEMA_ARG: &str = "--schema";
+
+/// The argument which tells `run` to print TypeScript definitions of the protocol, see
+/// `typescript`.
+pub const TYPESCRIPT_ARG: &str = "--typescript";
+
+/// The argument which tells `run` to use the `Encoding` named by the next argument for stdio.
+pub const ENCODING_ARG: &str = "--encoding";
+
+/// The argument which tells `run` to compress game states using the `Compression` named by the
+/// next argument for stdio.
+pub const COMPRESSION_ARG: &str = "--compression";
+
+/// How requests and responses are wrapped on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Bare `api::Request` and `api::Response` JSON.
+    Plain,
+    /// JSON-RPC 2.0 requests and responses, see the `jsonrpc` module.
+    JsonRpc,
+}
+
+impl Framing {
+    /// Handles a single framed request, returning `None` if no response should be written.
+    fn respond<R: Requester>(self, requester: &mut R, input: &[u8]) -> Option<String> {
+        match self {
+            Framing::Plain => Some(
+                serde_json::to_string(&respond(
+                    requester,
+                    serde_json::from_slice::<Request>(input).map_err(Error::from),
+                )).unwrap(),
+            ),
+            Framing::JsonRpc => jsonrpc::handle(requester, input)
+                .map(|resp| serde_json::to_string(&resp).unwrap()),
+        }
+    }
+}
 
 pub fn cli<R: Requester, I: Read, O: Write>(requester: &mut R, input: I, output: &mut O) {
-    writeln!(
-        output,
-        "{}",
-        serde_json::to_string(&match serde_json::from_reader::<_, Request>(input) {
-            Err(message) => Response::SystemError {
-                message: message.to_string(),
-            },
-            Ok(r) => requester.request(&r).unwrap(),
-        }).unwrap()
-    ).unwrap();
+    cli_framed(requester, input, output, Framing::Plain);
+}
+
+pub fn cli_framed<R: Requester, I: Read, O: Write>(
+    requester: &mut R,
+    mut input: I,
+    output: &mut O,
+    framing: Framing,
+) {
+    let mut buf = vec![];
+    input.read_to_end(&mut buf).unwrap();
+    if let Some(resp) = framing.respond(requester, &buf) {
+        writeln!(output, "{}", resp).unwrap();
+    }
+}
+
+/// Handles newline delimited requests from `input` until it or `output` is closed, writing a single
+/// line response for each.
+pub fn serve<R: Requester, I: BufRead, O: Write>(requester: &mut R, input: I, output: &mut O) {
+    serve_framed(requester, input, output, Framing::Plain);
+}
+
+pub fn serve_framed<R: Requester, I: BufRead, O: Write>(
+    requester: &mut R,
+    input: I,
+    output: &mut O,
+    framing: Framing,
+) {
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let resp = match framing.respond(requester, line.as_bytes()) {
+            Some(resp) => resp,
+            None => continue,
+        };
+        if writeln!(output, "{}", resp)
+            .and_then(|_| output.flush())
+            .is_err()
+        {
+            // The other end has gone away.
+            return;
+        }
+    }
+}
+
+/// Like `cli`, but using `codec` for the request and response. There is only one message in each
+/// direction, so binary messages aren't length prefixed.
+pub fn cli_encoded<R: Requester, I: Read, O: Write>(
+    requester: &mut R,
+    mut input: I,
+    output: &mut O,
+    codec: Codec,
+) {
+    let mut buf = vec![];
+    input.read_to_end(&mut buf).unwrap();
+    output
+        .write_all(&respond_encoded(requester, &buf, codec))
+        .unwrap();
+    if !codec.encoding.is_binary() {
+        writeln!(output).unwrap();
+    }
+}
+
+/// Like `serve`, but using `codec` for requests and responses. Binary encodings prefix each
+/// message with its length as a big endian `u32` instead of delimiting with newlines.
+pub fn serve_encoded<R: Requester, I: BufRead, O: Write>(
+    requester: &mut R,
+    mut input: I,
+    output: &mut O,
+    codec: Codec,
+) {
+    loop {
+        let req = if codec.encoding.is_binary() {
+            let len = match encoding::read_message_len(&mut input) {
+                Ok(Some(len)) => len,
+                Ok(None) | Err(_) => return,
+            };
+            let mut req = vec![0; len];
+            if input.read_exact(&mut req).is_err() {
+                return;
+            }
+            req
+        } else {
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => line.into_bytes(),
+            }
+        };
+        let resp = respond_encoded(requester, &req, codec);
+        if codec.encoding.write_message(output, &resp).is_err() {
+            // The other end has gone away.
+            return;
+        }
+    }
+}
+
+/// Listens for TCP connections, calling `serve_framed` for each on its own thread. Requests from
+/// all connections are passed to `requester` one at a time.
+pub fn serve_tcp<R: Requester + Send, A: ToSocketAddrs>(
+    requester: &mut R,
+    addr: A,
+    framing: Framing,
+) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr)?;
+    let requester = Mutex::new(requester);
+    thread::scope(|scope| -> Result<(), Error> {
+        for stream in listener.incoming() {
+            let mut stream = stream?;
+            stream.set_nodelay(true)?;
+            let reader = BufReader::new(stream.try_clone()?);
+            let mut requester = &requester;
+            scope.spawn(move || serve_framed(&mut requester, reader, &mut stream, framing));
+        }
+        Ok(())
+    })
+}
+
+/// Listens for Unix socket connections, calling `serve_framed` for each on its own thread.
+/// Requests from all connections are passed to `requester` one at a time. A socket left at `path`
+/// by a previous run is removed first, but only once connecting to it is refused; a socket that
+/// still accepts connections belongs to a live server and is left alone.
+#[cfg(unix)]
+pub fn serve_unix<R: Requester + Send, P: AsRef<Path>>(
+    requester: &mut R,
+    path: P,
+    framing: Framing,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    if fs::symlink_metadata(path).map_or(false, |m| m.file_type().is_socket()) {
+        match UnixStream::connect(path) {
+            Ok(_) => bail!("address in use: {}", path.display()),
+            Err(ref e) if e.kind() == ErrorKind::ConnectionRefused => fs::remove_file(path)?,
+            Err(_) => {}
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+    let requester = Mutex::new(requester);
+    thread::scope(|scope| -> Result<(), Error> {
+        for stream in listener.incoming() {
+            let mut stream = stream?;
+            let reader = BufReader::new(stream.try_clone()?);
+            let mut requester = &requester;
+            scope.spawn(move || serve_framed(&mut requester, reader, &mut stream, framing));
+        }
+        Ok(())
+    })
+}
+
+/// Entry point for game binaries. Calls `serve` if the first argument is `SERVE_ARG`, listens on a
+/// socket if it is `TCP_ARG`, `UNIX_ARG` or `HTTP_ARG`, prints the protocol schema if it is
+/// `SCHEMA_ARG` or `TYPESCRIPT_ARG`, otherwise calls `cli` using stdin and stdout. `JSONRPC_ARG`
+/// switches all but HTTP to JSON-RPC framing, `ENCODING_ARG` and `COMPRESSION_ARG` select a
+/// `Codec` for stdio.
+pub fn run<R: Requester + Send>(requester: &mut R) -> Result<(), Error> {
+    let mut args: Vec<String> = env::args().collect();
+    let framing = if args.iter().any(|a| a == JSONRPC_ARG) {
+        args.retain(|a| a != JSONRPC_ARG);
+        Framing::JsonRpc
+    } else {
+        Framing::Plain
+    };
+    let codec = Codec {
+        encoding: match take_arg_value(&mut args, ENCODING_ARG)? {
+            Some(name) => Encoding::from_name(&name)?,
+            None => Encoding::Json,
+        },
+        compression: match take_arg_value(&mut args, COMPRESSION_ARG)? {
+            Some(name) => Some(Compression::from_name(&name)?),
+            None => None,
+        },
+    };
+    let stdio = match args.get(1).map(String::as_str) {
+        Some(TCP_ARG) | Some(UNIX_ARG) | Some(HTTP_ARG) => false,
+        _ => true,
+    };
+    if !codec.is_default() && (framing == Framing::JsonRpc || !stdio) {
+        bail!("encodings are only supported for stdio without JSON-RPC framing");
+    }
+    let input = stdin();
+    let output = stdout();
+    match args.get(1).map(String::as_str) {
+        Some(SERVE_ARG) if framing == Framing::Plain => {
+            serve_encoded(requester, input.lock(), &mut output.lock(), codec)
+        }
+        Some(SERVE_ARG) => serve_framed(requester, input.lock(), &mut output.lock(), framing),
+        Some(TCP_ARG) => match args.get(2) {
+            Some(addr) => serve_tcp(requester, addr.as_str(), framing)?,
+            None => bail!("expected an address argument"),
+        },
+        #[cfg(unix)]
+        Some(UNIX_ARG) => match args.get(2) {
+            Some(path) => serve_unix(requester, path, framing)?,
+            None => bail!("expected a path argument"),
+        },
+        Some(HTTP_ARG) => match args.get(2) {
+            Some(addr) => http::serve(requester, addr.as_str())?,
+            None => bail!("expected an address argument"),
+        },
+        Some(SCHEMA_ARG) => writeln!(
+            output.lock(),
+            "{}",
+            serde_json::to_string_pretty(&schema::json_schema())?
+        )?,
+        Some(TYPESCRIPT_ARG) => write!(output.lock(), "{}", typescript::typescript())?,
+        _ if framing == Framing::Plain => {
+            cli_encoded(requester, input.lock(), &mut output.lock(), codec)
+        }
+        _ => cli_framed(requester, input.lock(), &mut output.lock(), framing),
+    }
+    Ok(())
+}
+
+/// Removes `name` and the value following it from `args`.
+fn take_arg_value(args: &mut Vec<String>, name: &str) -> Result<Option<String>, Error> {
+    match args.iter().position(|a| a == name) {
+        Some(i) if i + 1 < args.len() => {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            Ok(Some(value))
+        }
+        Some(_) => bail!("expected a value after {}", name),
+        None => Ok(None),
+    }
+}
+
+/// Handles an encoded request, advertising the supported encodings in `Response::Hello`.
+fn respond_encoded<R: Requester>(requester: &mut R, input: &[u8], codec: Codec) -> Vec<u8> {
+    let mut resp = respond(requester, codec.decode_request(input));
+    if let Response::Hello { ref mut hello } = resp {
+        hello.capabilities.extend(Codec::capabilities());
+    }
+    codec.encode_response(resp).unwrap_or_else(|e| {
+        codec
+            .encoding
+            .encode(&Response::SystemError {
+                message: e.to_string(),
+            }).unwrap()
+    })
+}
+
+fn respond<R: Requester>(requester: &mut R, request: Result<Request, Error>) -> Response {
+    match request {
+        Err(message) => Response::SystemError {
+            message: message.to_string(),
+        },
+        Ok(r) => requester
+            .request(&r)
+            .unwrap_or_else(|e| Response::SystemError {
+                message: e.to_string(),
+            }),
+    }
 }
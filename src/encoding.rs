@@ -0,0 +1,271 @@
+use base64;
+use failure::{bail, format_err, Error};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rmp_serde;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_cbor;
+use serde_json;
+use zstd;
+
+use std::io::{self, Read, Write};
+
+use crate::api::{Request, Response};
+use crate::cli::{COMPRESSION_ARG, ENCODING_ARG};
+
+/// How requests and responses are serialised between `LocalRequester` and `cli::run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Json
+    }
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::MessagePack => "msgpack",
+            Encoding::Cbor => "cbor",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Encoding, Error> {
+        Ok(match name {
+            "json" => Encoding::Json,
+            "msgpack" => Encoding::MessagePack,
+            "cbor" => Encoding::Cbor,
+            _ => bail!("expected an encoding of 'json', 'msgpack' or 'cbor'"),
+        })
+    }
+
+    /// The `Hello` capability advertising support, JSON is always supported.
+    pub fn capability(self) -> Option<&'static str> {
+        match self {
+            Encoding::Json => None,
+            Encoding::MessagePack => Some("MessagePack"),
+            Encoding::Cbor => Some("Cbor"),
+        }
+    }
+
+    /// Binary messages are length prefixed in `cli::serve`, JSON messages are newline delimited.
+    pub fn is_binary(self) -> bool {
+        self != Encoding::Json
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        Ok(match self {
+            Encoding::Json => serde_json::to_vec(value)?,
+            // Field names are kept so `#[serde(default)]` fields can be omitted.
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)?,
+            Encoding::Cbor => serde_cbor::to_vec(value)?,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, Error> {
+        Ok(match self {
+            Encoding::Json => serde_json::from_slice(bytes)?,
+            Encoding::MessagePack => rmp_serde::from_read_ref(bytes)?,
+            Encoding::Cbor => serde_cbor::from_slice(bytes)?,
+        })
+    }
+
+    /// Writes a single message for `cli::serve`.
+    pub fn write_message<W: Write>(self, w: &mut W, bytes: &[u8]) -> io::Result<()> {
+        if self.is_binary() {
+            w.write_all(&(bytes.len() as u32).to_be_bytes())?;
+            w.write_all(bytes)?;
+        } else {
+            w.write_all(bytes)?;
+            w.write_all(b"\n")?;
+        }
+        w.flush()
+    }
+}
+
+/// Reads the length prefix of a binary message, returning `None` if the stream is closed.
+pub fn read_message_len<R: Read>(r: &mut R) -> io::Result<Option<usize>> {
+    let mut len = [0; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => Ok(Some(u32::from_be_bytes(len) as usize)),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Compression applied to `GameResponse::state`. Compressed states are prefixed with the name of
+/// the compression and base64 encoded, so they remain strings in every encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Compression, Error> {
+        Ok(match name {
+            "gzip" => Compression::Gzip,
+            "zstd" => Compression::Zstd,
+            _ => bail!("expected a compression of 'gzip' or 'zstd'"),
+        })
+    }
+
+    /// The `Hello` capability advertising support.
+    pub fn capability(self) -> &'static str {
+        match self {
+            Compression::Gzip => "Gzip",
+            Compression::Zstd => "Zstd",
+        }
+    }
+
+    pub fn compress(self, state: &str) -> Result<String, Error> {
+        let compressed = match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(state.as_bytes())?;
+                encoder.finish()?
+            }
+            Compression::Zstd => zstd::encode_all(state.as_bytes(), 0)?,
+        };
+        Ok(format!("{}:{}", self.name(), base64::encode(&compressed)))
+    }
+}
+
+/// Decompresses a state compressed with `Compression::compress`, uncompressed states are returned
+/// unchanged.
+pub fn decompress_state(state: &str) -> Result<String, Error> {
+    let prefix = state
+        .find(':')
+        .map(|i| (Compression::from_name(&state[..i]), &state[i + 1..]));
+    let (compression, encoded) = match prefix {
+        Some((Ok(compression), encoded)) => (compression, encoded),
+        _ => return Ok(state.to_owned()),
+    };
+    let compressed = base64::decode(encoded)
+        .map_err(|e| format_err!("unable to decode compressed game state: {}", e))?;
+    let decompressed = match compression {
+        Compression::Gzip => {
+            let mut decompressed = vec![];
+            GzDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed)?;
+            decompressed
+        }
+        Compression::Zstd => zstd::decode_all(compressed.as_slice())?,
+    };
+    String::from_utf8(decompressed)
+        .map_err(|e| format_err!("unable to decode compressed game state: {}", e))
+}
+
+/// An encoding and optional state compression, negotiated using `Hello::capabilities`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Codec {
+    pub encoding: Encoding,
+    pub compression: Option<Compression>,
+}
+
+impl Codec {
+    pub fn is_default(&self) -> bool {
+        *self == Codec::default()
+    }
+
+    /// Every capability supported by `cli::run`.
+    pub fn capabilities() -> Vec<String> {
+        let mut capabilities: Vec<String> = [Encoding::MessagePack, Encoding::Cbor]
+            .iter()
+            .filter_map(|e| e.capability())
+            .map(String::from)
+            .collect();
+        capabilities.extend(
+            [Compression::Gzip, Compression::Zstd]
+                .iter()
+                .map(|c| c.capability().to_string()),
+        );
+        capabilities
+    }
+
+    /// Falls back to JSON or no compression for anything missing from `capabilities`.
+    pub fn negotiate(&self, capabilities: &[String]) -> Codec {
+        let supported = |capability: &str| capabilities.iter().any(|c| c == capability);
+        Codec {
+            encoding: match self.encoding.capability() {
+                Some(capability) if !supported(capability) => Encoding::Json,
+                _ => self.encoding,
+            },
+            compression: self
+                .compression
+                .filter(|compression| supported(compression.capability())),
+        }
+    }
+
+    /// The arguments which select this codec in `cli::run`.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if self.encoding != Encoding::Json {
+            args.push(ENCODING_ARG.to_string());
+            args.push(self.encoding.name().to_string());
+        }
+        if let Some(compression) = self.compression {
+            args.push(COMPRESSION_ARG.to_string());
+            args.push(compression.name().to_string());
+        }
+        args
+    }
+
+    /// Decodes a request, decompressing any game states it contains.
+    pub fn decode_request(&self, bytes: &[u8]) -> Result<Request, Error> {
+        let mut req = self.encoding.decode(bytes)?;
+        decompress_request(&mut req)?;
+        Ok(req)
+    }
+
+    /// Encodes a response, compressing any game states it contains.
+    pub fn encode_response(&self, mut resp: Response) -> Result<Vec<u8>, Error> {
+        if let Some(compression) = self.compression {
+            compress_response(&mut resp, compression)?;
+        }
+        self.encoding.encode(&resp)
+    }
+}
+
+fn decompress_request(req: &mut Request) -> Result<(), Error> {
+    if let Request::Batch {
+        ref mut requests, ..
+    } = *req
+    {
+        for req in requests {
+            decompress_request(req)?;
+        }
+    }
+    if let Some(game) = req.game_state_mut() {
+        *game = decompress_state(game)?;
+    }
+    Ok(())
+}
+
+fn compress_response(resp: &mut Response, compression: Compression) -> Result<(), Error> {
+    if let Response::Batch {
+        ref mut responses,
+    } = *resp
+    {
+        for resp in responses {
+            compress_response(resp, compression)?;
+        }
+    }
+    if let Some(game) = resp.game_state_mut() {
+        *game = compression.compress(game)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use failure::{bail, format_err, Error};
+use serde_json::{self, Value};
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::api::{Request, Response};
+use crate::requester::Requester;
+
+/// Request bodies larger than this are rejected.
+const MAX_BODY_LEN: usize = 64 * 1024 * 1024;
+
+/// Request lines and headers longer than this are rejected.
+const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// Connections which stall for longer than this while reading or writing are closed.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The route accepting any `api::Request`, other routes are the snake case name of a request
+/// variant and take the variant's fields as the body, eg. `POST /player_render`.
+pub const REQUEST_PATH: &str = "/request";
+
+/// Listens for HTTP connections, passing requests to `requester`. Connections are handled on
+/// separate threads and closed after each response, requests are passed to `requester` one at a
+/// time.
+pub fn serve<R: Requester + Send, A: ToSocketAddrs>(
+    requester: &mut R,
+    addr: A,
+) -> Result<(), Error> {
+    serve_listener(requester, TcpListener::bind(addr)?)
+}
+
+/// Like `serve`, but using a listener which is already bound.
+pub fn serve_listener<R: Requester + Send>(
+    requester: &mut R,
+    listener: TcpListener,
+) -> Result<(), Error> {
+    let requester = Mutex::new(requester);
+    thread::scope(|scope| -> Result<(), Error> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let mut requester = &requester;
+            scope.spawn(move || {
+                // A bad connection shouldn't take the server down.
+                let _ = handle_conn(&mut requester, stream);
+            });
+        }
+        Ok(())
+    })
+}
+
+fn handle_conn<R: Requester>(requester: &mut R, mut stream: TcpStream) -> Result<(), Error> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    read_line(&mut reader, &mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+    let content_length = read_headers(&mut reader)?;
+    if content_length > MAX_BODY_LEN {
+        return write_response(
+            &mut stream,
+            "413 Payload Too Large",
+            &system_error("request body too large"),
+        );
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let (status, resp) = route(requester, &method, &path, &body);
+    write_response(&mut stream, status, &resp)
+}
+
+fn route<R: Requester>(
+    requester: &mut R,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> (&'static str, Response) {
+    if method != "POST" {
+        return ("405 Method Not Allowed", system_error("expected POST"));
+    }
+    let parsed = if path == REQUEST_PATH {
+        serde_json::from_slice::<Request>(body)
+    } else {
+        let variant = match variant_name(path) {
+            Some(ref variant) if Request::NAMES.contains(&variant.as_str()) => variant.to_owned(),
+            _ => return ("404 Not Found", system_error("unknown path")),
+        };
+        if body.iter().all(u8::is_ascii_whitespace) {
+            Request::from_parts(&variant, None)
+        } else {
+            serde_json::from_slice::<Value>(body)
+                .and_then(|fields| Request::from_parts(&variant, Some(fields)))
+        }
+    };
+    match parsed {
+        Ok(req) => match requester.request(&req) {
+            Ok(resp) => ("200 OK", resp),
+            Err(e) => ("500 Internal Server Error", system_error(e)),
+        },
+        Err(e) => ("400 Bad Request", system_error(e)),
+    }
+}
+
+/// Converts a path such as `/player_render` to a variant name such as `PlayerRender`.
+fn variant_name(path: &str) -> Option<String> {
+    let name = path.trim_start_matches('/');
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        return None;
+    }
+    Some(
+        name.split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect(),
+    )
+}
+
+fn system_error<M: ToString>(message: M) -> Response {
+    Response::SystemError {
+        message: message.to_string(),
+    }
+}
+
+fn write_response<W: Write>(w: &mut W, status: &str, resp: &Response) -> Result<(), Error> {
+    let body = serde_json::to_vec(resp)?;
+    write!(
+        w,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    w.write_all(&body)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Reads headers up to the blank line, returning the content length.
+pub(crate) fn read_headers<R: BufRead>(r: &mut R) -> Result<usize, Error> {
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if read_line(r, &mut line)? == 0 {
+            bail!("connection closed while reading headers");
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(content_length);
+        }
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or_default();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = header
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .parse()
+                .map_err(|e| format_err!("invalid content length: {}", e))?;
+        }
+    }
+}
+
+/// Like `BufRead::read_line`, but failing if the line is longer than `MAX_LINE_LEN`.
+fn read_line<R: BufRead>(r: &mut R, line: &mut String) -> Result<usize, Error> {
+    let read = r.by_ref().take(MAX_LINE_LEN as u64 + 1).read_line(line)?;
+    if line.len() > MAX_LINE_LEN {
+        bail!("line longer than {} bytes", MAX_LINE_LEN);
+    }
+    Ok(read)
+}
@@ -0,0 +1,142 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{self, json, Value};
+
+use crate::api::{Request, Response};
+use crate::requester::Requester;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// Returned for `Response::UserError`, the error data contains its kind, code and span.
+pub const USER_ERROR: i64 = -32000;
+/// Returned for `Response::SystemError`.
+pub const SYSTEM_ERROR: i64 = -32001;
+/// Returned for `Response::StateVersionError`, the error data contains both state versions.
+pub const STATE_VERSION_ERROR: i64 = -32002;
+
+/// A JSON-RPC 2.0 request, `method` is the name of a request variant and `params` are its fields.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Option<Value>,
+}
+
+/// A JSON-RPC 2.0 response, `result` is the externally tagged `api::Response`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Response>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcResponse {
+    fn result(id: Value, result: Response) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error<M: ToString>(id: Value, code: i64, message: M, data: Option<Value>) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.to_string(),
+                data,
+            }),
+        }
+    }
+}
+
+/// Handles a single JSON-RPC request, returning `None` for notifications.
+pub fn handle<R: Requester>(requester: &mut R, input: &[u8]) -> Option<RpcResponse> {
+    let value: Value = match serde_json::from_slice(input) {
+        Ok(value) => value,
+        Err(e) => return Some(RpcResponse::error(Value::Null, PARSE_ERROR, e, None)),
+    };
+    // A request is a notification when it has no id member at all, an explicit null id still
+    // gets a response.
+    let notification = value.get("id").is_none();
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let rpc_req: RpcRequest = match serde_json::from_value(value) {
+        Ok(ref r) if r.jsonrpc != "2.0" => {
+            return Some(RpcResponse::error(
+                id,
+                INVALID_REQUEST,
+                "expected jsonrpc to be \"2.0\"",
+                None,
+            ))
+        }
+        Ok(r) => r,
+        Err(e) => return Some(RpcResponse::error(id, INVALID_REQUEST, e, None)),
+    };
+    let resp = if !Request::NAMES.contains(&rpc_req.method.as_str()) {
+        RpcResponse::error(id, METHOD_NOT_FOUND, "method not found", None)
+    } else {
+        match Request::from_parts(&rpc_req.method, rpc_req.params) {
+            Err(e) => RpcResponse::error(id, INVALID_PARAMS, e, None),
+            Ok(req) => match requester.request(&req) {
+                Err(e) => RpcResponse::error(id, INTERNAL_ERROR, e, None),
+                Ok(Response::UserError {
+                    message,
+                    kind,
+                    code,
+                    span,
+                }) => RpcResponse::error(
+                    id,
+                    USER_ERROR,
+                    message,
+                    Some(json!({
+                        "kind": kind,
+                        "code": code,
+                        "span": span,
+                    })),
+                ),
+                Ok(Response::SystemError { message }) => {
+                    RpcResponse::error(id, SYSTEM_ERROR, message, None)
+                }
+                Ok(Response::StateVersionError {
+                    state_version,
+                    current_version,
+                    message,
+                }) => RpcResponse::error(
+                    id,
+                    STATE_VERSION_ERROR,
+                    message,
+                    Some(json!({
+                        "state_version": state_version,
+                        "current_version": current_version,
+                    })),
+                ),
+                Ok(resp) => RpcResponse::result(id, resp),
+            },
+        }
+    };
+    if notification {
+        None
+    } else {
+        Some(resp)
+    }
+}
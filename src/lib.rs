@@ -6,4 +6,11 @@ pub use crate::repl::repl;
 pub mod api;
 pub mod bot_cli;
 pub mod cli;
+pub mod encoding;
+pub mod http;
+pub mod jsonrpc;
+pub mod render;
 pub mod requester;
+pub mod schema;
+pub mod suggest;
+pub mod typescript;
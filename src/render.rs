@@ -0,0 +1,183 @@
+use std::iter::repeat;
+
+use brdgme_color::{player_color, Color, Style};
+use brdgme_markup::{self, ansi, from_lines, to_lines, transform, Node, Player, TNode};
+
+use crate::api::RenderFormat;
+
+/// The size of a character cell in `svg` output, in pixels.
+const SVG_CELL_WIDTH: usize = 10;
+const SVG_CELL_HEIGHT: usize = 20;
+const SVG_FONT_SIZE: usize = 16;
+/// The distance from the top of a cell to the text baseline.
+const SVG_BASELINE: usize = 15;
+
+/// Builds players for `transform`, using a placeholder name for any player missing from `names`.
+pub fn players(names: &[String], player_count: usize) -> Vec<Player> {
+    (0..player_count)
+        .map(|p| Player {
+            name: names
+                .get(p)
+                .cloned()
+                .unwrap_or_else(|| format!("Player {}", p + 1)),
+            color: player_color(p).to_owned(),
+        })
+        .collect()
+}
+
+/// Transforms markup nodes, padding each line with the default background up to `width`.
+pub fn transform_width(nodes: &[Node], players: &[Player], width: Option<usize>) -> Vec<TNode> {
+    let transformed = transform(nodes, players);
+    let width = match width {
+        Some(width) => width,
+        None => return transformed,
+    };
+    from_lines(
+        &to_lines(&transformed)
+            .iter()
+            .map(|l| {
+                let l_len = TNode::len(l);
+                let mut l = l.to_owned();
+                if l_len < width {
+                    l.push(TNode::Bg(
+                        *Style::default().bg,
+                        vec![TNode::Text(repeat(" ").take(width - l_len).collect())],
+                    ));
+                }
+                l
+            })
+            .collect::<Vec<Vec<TNode>>>(),
+    )
+}
+
+/// Renders markup nodes in `format`. `width` is ignored for `RenderFormat::Markup` as markup is
+/// returned untransformed.
+pub fn render(
+    nodes: &[Node],
+    format: RenderFormat,
+    players: &[Player],
+    width: Option<usize>,
+) -> String {
+    match format {
+        RenderFormat::Markup => brdgme_markup::to_string(nodes),
+        RenderFormat::Ansi => ansi(&transform_width(nodes, players, width)),
+        RenderFormat::Plain => brdgme_markup::plain(&transform_width(nodes, players, width)),
+        RenderFormat::Html => brdgme_markup::html(&transform_width(nodes, players, width)),
+        RenderFormat::Svg => svg(&transform_width(nodes, players, width)),
+    }
+}
+
+/// A run of text in a single style, starting at column `col`.
+struct SvgRun {
+    col: usize,
+    text: String,
+    fg: String,
+    bg: String,
+    bold: bool,
+}
+
+/// Renders transformed markup as a standalone SVG document. Each character occupies a fixed size
+/// cell so the layout matches a terminal.
+pub fn svg(nodes: &[TNode]) -> String {
+    let default_fg = hex(&Style::default().fg);
+    let default_bg = hex(&Style::default().bg);
+    let lines = to_lines(nodes)
+        .iter()
+        .map(|l| {
+            let mut runs = vec![];
+            let mut col = 0;
+            svg_runs(l, &default_fg, &default_bg, false, &mut col, &mut runs);
+            runs
+        })
+        .collect::<Vec<Vec<SvgRun>>>();
+    let cols = lines
+        .iter()
+        .filter_map(|l| l.last().map(|r| r.col + r.text.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let width = cols * SVG_CELL_WIDTH;
+    let height = lines.len() * SVG_CELL_HEIGHT;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"monospace\" font-size=\"{fs}\">\n",
+        w = width,
+        h = height,
+        fs = SVG_FONT_SIZE,
+    );
+    out.push_str(&format!(
+        "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+        width, height, default_bg
+    ));
+    for (row, runs) in lines.iter().enumerate() {
+        let y = row * SVG_CELL_HEIGHT;
+        for run in runs {
+            let x = run.col * SVG_CELL_WIDTH;
+            let run_width = run.text.chars().count() * SVG_CELL_WIDTH;
+            if run.bg != default_bg {
+                out.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                    x, y, run_width, SVG_CELL_HEIGHT, run.bg
+                ));
+            }
+            if run.text.trim().is_empty() {
+                continue;
+            }
+            // textLength pins each run to the cell grid regardless of the font's advance width.
+            out.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"{}\"{} textLength=\"{}\" \
+                 lengthAdjust=\"spacingAndGlyphs\" xml:space=\"preserve\">{}</text>\n",
+                x,
+                y + SVG_BASELINE,
+                run.fg,
+                if run.bold { " font-weight=\"bold\"" } else { "" },
+                run_width,
+                escape_xml(&run.text)
+            ));
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn svg_runs(
+    nodes: &[TNode],
+    fg: &str,
+    bg: &str,
+    bold: bool,
+    col: &mut usize,
+    runs: &mut Vec<SvgRun>,
+) {
+    for n in nodes {
+        match *n {
+            TNode::Text(ref text) => {
+                runs.push(SvgRun {
+                    col: *col,
+                    text: text.to_owned(),
+                    fg: fg.to_owned(),
+                    bg: bg.to_owned(),
+                    bold,
+                });
+                *col += text.chars().count();
+            }
+            TNode::Fg(ref color, ref children) => {
+                svg_runs(children, &hex(color), bg, bold, col, runs)
+            }
+            TNode::Bg(ref color, ref children) => {
+                svg_runs(children, fg, &hex(color), bold, col, runs)
+            }
+            TNode::Bold(ref children) => svg_runs(children, fg, bg, true, col, runs),
+        }
+    }
+}
+
+fn hex(color: &Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
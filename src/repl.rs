@@ -7,14 +7,15 @@ use std::borrow::Cow;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{stdin, stdout};
-use std::iter::repeat;
+use std::mem;
 
 use brdgme_color::{player_color, Style};
 use brdgme_game::command::doc;
 use brdgme_game::Status;
-use brdgme_markup::{self, ansi, from_lines, to_lines, transform, Node, Player, TNode};
+use brdgme_markup::{self, ansi, transform, Node, Player};
 
 use crate::api::{CliLog, GameResponse, Request, Response};
+use crate::render;
 use crate::requester::Requester;
 
 pub fn repl<T>(client: &mut T)
@@ -44,6 +45,8 @@ where
     let (mut game, logs, mut public_render, mut player_renders) = match client
         .request(&Request::New {
             players: players.len(),
+            seed: None,
+            options: None,
         })
         .unwrap()
     {
@@ -52,16 +55,33 @@ where
             logs,
             public_render,
             player_renders,
+            ..
         } => (game, logs, public_render, player_renders),
-        Response::UserError { message } | Response::SystemError { message } => panic!(message),
+        Response::UserError { message, .. } | Response::SystemError { message } => panic!(message),
         _ => panic!("wrong reponse"),
     };
+    // Private logs are held back until it is the recipient's turn at the keyboard.
+    let mut private_logs: Vec<Vec<CliLog>> = vec![vec![]; players.len()];
     output_nl();
-    output_logs(logs, &players);
+    route_logs(logs, &mut private_logs, &players);
     let mut undo_stack: Vec<GameResponse> = vec![game.clone()];
     loop {
         match game.status.clone() {
             Status::Finished { placings, .. } => {
+                // Nobody gets another turn, so show any private logs still held back.
+                for (player, pending) in private_logs.iter_mut().enumerate() {
+                    if !pending.is_empty() {
+                        output_nl();
+                        output_nodes(
+                            &[Node::Bold(vec![
+                                Node::text("Logs for "),
+                                Node::Player(player),
+                            ])],
+                            &players,
+                        );
+                        output_logs(mem::replace(pending, vec![]), &players);
+                    }
+                }
                 output_nl();
                 match placings.as_slice() {
                     placings if placings.is_empty() => {
@@ -90,6 +110,12 @@ where
                     return;
                 }
                 let current_player = whose_turn[0];
+                if let Some(pending) = private_logs.get_mut(current_player) {
+                    if !pending.is_empty() {
+                        output_logs(mem::replace(pending, vec![]), &players);
+                        output_nl();
+                    }
+                }
                 output_markup(&player_renders[current_player].render, &players);
                 println!();
                 if let Some(ref spec) = player_renders[current_player].command_spec {
@@ -127,13 +153,45 @@ where
                             );
                         }
                     }
+                    i if i.starts_with(":svg") => {
+                        let path = match i[":svg".len()..].trim() {
+                            "" => "game.svg",
+                            path => path,
+                        };
+                        let nodes = brdgme_markup::from_string(&public_render.render).unwrap().0;
+                        let mut file = File::create(path).expect("could not create file");
+                        write!(file, "{}", render::svg(&transform(&nodes, &players)))
+                            .expect("could not write to file");
+                    }
                     ":quit" | ":q" => return,
+                    i if i.starts_with(":suggest") => match client
+                        .request(&Request::Suggest {
+                            player: current_player,
+                            partial_input: i[":suggest".len()..].trim_start().to_owned(),
+                            names: player_names.clone(),
+                            game: game.state.clone(),
+                        })
+                        .unwrap()
+                    {
+                        Response::Suggest { suggestions, .. } => {
+                            output_nl();
+                            for s in suggestions {
+                                output_nodes(&[Node::text(s.command)], &players);
+                            }
+                        }
+                        Response::UserError { message, .. } | Response::SystemError { message } => {
+                            output_nl();
+                            output_error(message);
+                        }
+                        _ => panic!("unexpected response"),
+                    },
                     _ => match client
                         .request(&Request::Play {
                             player: current_player,
                             command: input,
                             names: player_names.clone(),
                             game: game.state.clone(),
+                            diff: false,
                         })
                         .unwrap()
                     {
@@ -155,13 +213,13 @@ where
                             public_render = new_public_render;
                             player_renders = new_player_renders;
                             output_nl();
-                            output_logs(logs, &players);
+                            route_logs(logs, &mut private_logs, &players);
                         }
                         Response::SystemError { message } => {
                             output_nl();
                             panic!(message);
                         }
-                        Response::UserError { message } => {
+                        Response::UserError { message, .. } => {
                             output_nl();
                             output_error(message);
                         }
@@ -173,6 +231,17 @@ where
     }
 }
 
+fn route_logs(logs: Vec<CliLog>, private_logs: &mut [Vec<CliLog>], players: &[Player]) {
+    for l in logs.iter().filter(|l| !l.public) {
+        for &p in &l.to {
+            if let Some(pl) = private_logs.get_mut(p) {
+                pl.push(l.clone());
+            }
+        }
+    }
+    output_logs(CliLog::filter_for(&logs, None), players);
+}
+
 fn output_logs(logs: Vec<CliLog>, players: &[Player]) {
     for l in logs {
         let (content, _) = brdgme_markup::from_string(&l.content).unwrap();
@@ -185,23 +254,7 @@ fn output_logs(logs: Vec<CliLog>, players: &[Player]) {
 
 fn output_nodes(nodes: &[Node], players: &[Player]) {
     let (term_w, _) = term_size::dimensions().unwrap_or_default();
-    print!(
-        "{}",
-        ansi(&from_lines(&to_lines(&transform(nodes, players))
-            .iter()
-            .map(|l| {
-                let l_len = TNode::len(l);
-                let mut l = l.to_owned();
-                if l_len < term_w {
-                    l.push(TNode::Bg(
-                        *Style::default().bg,
-                        vec![TNode::Text(repeat(" ").take(term_w - l_len).collect())],
-                    ));
-                }
-                l
-            })
-            .collect::<Vec<Vec<TNode>>>()))
-    );
+    print!("{}", ansi(&render::transform_width(nodes, players, Some(term_w))));
 }
 
 fn output_error<I: Into<String>>(s: I) {
@@ -1,53 +1,554 @@
-use failure::Error;
+use failure::{format_err, Error, Fail};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use serde_json;
+use serde_json::{self, Value};
 
 use std::fmt::Debug;
+use std::any::Any;
 use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use brdgme_game::errors::GameError;
-use brdgme_game::{CommandResponse, Gamer, Renderer};
-use brdgme_markup;
+use brdgme_game::{CommandResponse, Gamer, Log, Renderer};
 
-use crate::api::{CliLog, GameResponse, PlayerRender, PubRender, Request, Response};
+use crate::api::{
+    self, CliLog, ErrorKind, GameOption, GameResponse, Hello, Info, PlayerRender, PubRender,
+    RenderFormat, ReplayCommand, Request, Response, Snapshot, Span, StateFormat, StateKey,
+    PROTOCOL_VERSION,
+};
+use crate::render;
 use crate::requester::Requester;
+use crate::suggest;
+
+/// Creates a new game using the given RNG seed, used for `Request::New` when provided.
+pub type SeededNew<G> = fn(players: usize, seed: u64) -> Result<(G, Vec<Log>), GameError>;
+
+/// Creates a new game using validated options, a seed is provided when the game also supports
+/// seeding via `GameRequester::seeded`.
+pub type OptionsNew<G> =
+    fn(players: usize, seed: Option<u64>, options: &Value) -> Result<(G, Vec<Log>), GameError>;
+
+/// Upgrades a decoded game state from `from_version` to the current state version, returning a
+/// message describing why if it can't be migrated.
+pub type Migrate = fn(game: Value, from_version: u32) -> Result<Value, String>;
+
+/// Returned when a game state was encoded with a different state version, converted to
+/// `Response::StateVersionError`.
+#[derive(Debug, Fail)]
+#[fail(display = "{}", message)]
+pub struct StateVersionError {
+    pub state_version: u32,
+    pub current_version: u32,
+    pub message: String,
+}
+
+impl StateVersionError {
+    fn new<M: Into<String>>(state_version: u32, current_version: u32, message: M) -> Self {
+        StateVersionError {
+            state_version,
+            current_version,
+            message: message.into(),
+        }
+    }
+}
 
 pub struct GameRequester<G: Gamer + Debug + Clone + Serialize + DeserializeOwned> {
     gamer: PhantomData<G>,
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    rules: Option<String>,
+    duration: Option<u32>,
+    seeded_new: Option<SeededNew<G>>,
+    options: Vec<GameOption>,
+    options_new: Option<OptionsNew<G>>,
+    format: StateFormat,
+    migrate: Option<Migrate>,
 }
 
 pub fn new<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>() -> GameRequester<G> {
-    GameRequester { gamer: PhantomData }
+    GameRequester {
+        gamer: PhantomData,
+        name: None,
+        version: None,
+        description: None,
+        rules: None,
+        duration: None,
+        seeded_new: None,
+        options: vec![],
+        options_new: None,
+        format: StateFormat::default(),
+        migrate: None,
+    }
 }
 
-impl<G: Gamer + Debug + Clone + Serialize + DeserializeOwned> Requester for GameRequester<G> {
-    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+impl<G: Gamer + Debug + Clone + Serialize + DeserializeOwned> GameRequester<G> {
+    /// Sets the game name and version reported by `Request::Hello` and `Request::Info`, usually
+    /// `env!("CARGO_PKG_NAME")` and `env!("CARGO_PKG_VERSION")`.
+    pub fn named<N: Into<String>, V: Into<String>>(mut self, name: N, version: V) -> Self {
+        self.name = Some(name.into());
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Sets a short description of the game reported by `Request::Info`.
+    pub fn description<D: Into<String>>(mut self, description: D) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the rules reported by `Request::Info`, in brdgme markup.
+    pub fn rules<R: Into<String>>(mut self, rules: R) -> Self {
+        self.rules = Some(rules.into());
+        self
+    }
+
+    /// Sets the estimated duration of a game in minutes reported by `Request::Info`.
+    pub fn duration(mut self, minutes: u32) -> Self {
+        self.duration = Some(minutes);
+        self
+    }
+
+    /// Enables reproducible game creation by seeding the game RNG.
+    pub fn seeded(mut self, seeded_new: SeededNew<G>) -> Self {
+        self.seeded_new = Some(seeded_new);
+        self
+    }
+
+    /// Enables game options, `options_new` is used to create all games with options validated
+    /// against `options`.
+    pub fn options(mut self, options: Vec<GameOption>, options_new: OptionsNew<G>) -> Self {
+        self.options = options;
+        self.options_new = Some(options_new);
+        self
+    }
+
+    /// Sets the version of the game's state layout, which should be bumped whenever a change to the
+    /// game type would stop existing states from decoding. `migrate` upgrades states from earlier
+    /// versions for `Request::Migrate`.
+    pub fn versioned(mut self, state_version: u32, migrate: Migrate) -> Self {
+        self.format.state_version = state_version;
+        self.migrate = Some(migrate);
+        self
+    }
+
+    /// Signs game states with `key`, requests with unsigned or tampered states are rejected.
+    pub fn signed(mut self, key: StateKey) -> Self {
+        self.format.key = Some(key);
+        self
+    }
+
+    /// Signs game states with the key from `StateKey::from_env` if one is configured.
+    pub fn signed_from_env(self) -> Result<Self, Error> {
+        Ok(match StateKey::from_env()? {
+            Some(key) => self.signed(key),
+            None => self,
+        })
+    }
+
+    fn capabilities(&self) -> Vec<String> {
+        let mut capabilities = vec![
+            "Hello".to_string(),
+            "Options".to_string(),
+            "Replay".to_string(),
+            "Validate".to_string(),
+            "Suggest".to_string(),
+            "Batch".to_string(),
+            "Diff".to_string(),
+            "Logs".to_string(),
+            "Info".to_string(),
+            "RenderFormats".to_string(),
+        ];
+        if self.seeded_new.is_some() {
+            capabilities.push("Seed".to_string());
+        }
+        if self.migrate.is_some() {
+            capabilities.push("Migrate".to_string());
+        }
+        capabilities
+    }
+
+    /// Creates a new game, returning the seed and validated options used to create it.
+    fn create(
+        &self,
+        players: usize,
+        seed: Option<u64>,
+        options: Option<&Value>,
+    ) -> Result<(G, Vec<Log>, Option<u64>, Option<Value>), Response> {
+        let seed = match (self.seeded_new, seed) {
+            (Some(_), seed) => Some(seed.unwrap_or_else(random_seed)),
+            (None, Some(_)) => {
+                return Err(Response::SystemError {
+                    message: "this game does not support seeded creation".to_string(),
+                })
+            }
+            (None, None) => None,
+        };
+        let (created, options) = match self.options_new {
+            Some(options_new) => match api::validate_options(&self.options, options) {
+                Ok(options) => (options_new(players, seed, &options), Some(options)),
+                Err(message) => {
+                    return Err(Response::user_error(
+                        ErrorKind::InvalidOptions,
+                        message,
+                        None,
+                    ))
+                }
+            },
+            None if options.map_or(false, |o| !o.is_null()) => {
+                return Err(Response::SystemError {
+                    message: "this game does not support options".to_string(),
+                })
+            }
+            None => match (self.seeded_new, seed) {
+                (Some(seeded_new), Some(seed)) => (seeded_new(players, seed), None),
+                _ => (G::new(players), None),
+            },
+        };
+        created
+            .map(|(game, logs)| (game, logs, seed, options))
+            .map_err(Response::from)
+    }
+
+    fn handle_new(
+        &self,
+        players: usize,
+        seed: Option<u64>,
+        options: Option<&Value>,
+    ) -> Result<Response, Error> {
+        let (game, logs, seed, options) = match self.create(players, seed, options) {
+            Ok(created) => created,
+            Err(resp) => return Ok(resp),
+        };
+        let mut gs = GameResponse::from_gamer_with(&game, &self.format)?;
+        gs.options = options;
+        let (public_render, player_renders) = renders(&game)?;
+        Ok(Response::New {
+            game: gs,
+            logs: CliLog::from_logs(&logs),
+            seed,
+            public_render,
+            player_renders,
+        })
+    }
+
+    fn handle_replay(
+        &self,
+        players: usize,
+        seed: Option<u64>,
+        options: Option<&Value>,
+        names: &[String],
+        commands: &[ReplayCommand],
+    ) -> Result<Response, Error> {
+        if self.seeded_new.is_some() && seed.is_none() {
+            return Ok(Response::SystemError {
+                message: "a seed is required to replay this game".to_string(),
+            });
+        }
+        let (mut game, logs, _, options) = match self.create(players, seed, options) {
+            Ok(created) => created,
+            Err(resp) => return Ok(resp),
+        };
+        let mut command_logs = vec![];
+        for (index, c) in commands.iter().enumerate() {
+            match game.command(c.player, &c.command, names) {
+                Ok(CommandResponse {
+                    logs,
+                    remaining_input,
+                    ..
+                }) => {
+                    if !remaining_input.trim().is_empty() {
+                        let start = c.command.len().saturating_sub(remaining_input.len());
+                        return Ok(Response::ReplayError {
+                            index,
+                            error: Box::new(Response::user_error(
+                                ErrorKind::UnexpectedInput,
+                                format!("Unexpected: '{}'", remaining_input),
+                                Some(Span {
+                                    start,
+                                    end: c.command.len(),
+                                }),
+                            )),
+                        });
+                    }
+                    command_logs.push(CliLog::from_logs(&logs));
+                }
+                Err(e) => {
+                    return Ok(Response::ReplayError {
+                        index,
+                        error: Box::new(Response::from_game_error(e, &c.command)),
+                    })
+                }
+            }
+        }
+        let mut gr = GameResponse::from_gamer_with(&game, &self.format)?;
+        gr.options = options;
+        let (public_render, player_renders) = renders(&game)?;
+        Ok(Response::Replay {
+            game: gr,
+            logs: CliLog::from_logs(&logs),
+            command_logs,
+            public_render,
+            player_renders,
+        })
+    }
+
+    /// Verifies and decodes a game state, failing with a `StateVersionError` if it was encoded with
+    /// a different state version.
+    fn parse_game(&self, game: &str) -> Result<G, Error> {
+        let envelope = self.format.decode(game)?;
+        let state_version = self.format.state_version;
+        if envelope.state_version != state_version {
+            return Err(StateVersionError::new(
+                envelope.state_version,
+                state_version,
+                format!(
+                    "game state is version {} but the game uses version {}",
+                    envelope.state_version, state_version
+                ),
+            ).into());
+        }
+        serde_json::from_value(envelope.game)
+            .map_err(|e| format_err!("unable to decode game state: {}", e))
+    }
+
+    fn handle_migrate(&self, game: &str, from_version: Option<u32>) -> Result<Response, Error> {
+        let envelope = self.format.decode(game)?;
+        let state_version = self.format.state_version;
+        let from_version = from_version.unwrap_or(envelope.state_version);
+        let unmigratable =
+            |message: String| StateVersionError::new(from_version, state_version, message);
+        let migrated = if from_version == state_version {
+            envelope.game
+        } else if from_version > state_version {
+            return Err(unmigratable(format!(
+                "game state is version {} which is newer than the game's version {}",
+                from_version, state_version
+            )).into());
+        } else {
+            match self.migrate {
+                Some(migrate) => migrate(envelope.game, from_version).map_err(|message| {
+                    unmigratable(format!(
+                        "unable to migrate game state from version {}: {}",
+                        from_version, message
+                    ))
+                })?,
+                None => {
+                    return Err(unmigratable(
+                        "this game does not support migrating game states".to_string(),
+                    ).into())
+                }
+            }
+        };
+        let game: G = serde_json::from_value(migrated).map_err(|e| {
+            unmigratable(format!(
+                "unable to decode game state migrated from version {}: {}",
+                from_version, e
+            ))
+        })?;
+        let (public_render, player_renders) = renders(&game)?;
+        Ok(Response::Migrate {
+            game: GameResponse::from_gamer_with(&game, &self.format)?,
+            public_render,
+            player_renders,
+        })
+    }
+
+    fn handle_hello(&self) -> Response {
+        Response::Hello {
+            hello: Hello {
+                protocol_version: PROTOCOL_VERSION,
+                name: self.name.clone(),
+                version: self.version.clone(),
+                capabilities: self.capabilities(),
+            },
+        }
+    }
+
+    fn handle(&self, req: &Request) -> Result<Response, Error> {
         match *req {
-            Request::New { players } => Ok(handle_new::<G>(players)),
+            Request::Hello => Ok(self.handle_hello()),
+            Request::New {
+                players,
+                seed,
+                ref options,
+            } => self.handle_new(players, seed, options.as_ref()),
+            Request::Replay {
+                players,
+                seed,
+                ref options,
+                ref names,
+                ref commands,
+            } => self.handle_replay(players, seed, options.as_ref(), names, commands),
+            Request::Options => Ok(Response::Options {
+                options: self.options.clone(),
+            }),
             Request::PlayerCounts => Ok(handle_player_counts::<G>()),
+            Request::Info => Ok(Response::Info {
+                info: Info {
+                    name: self.name.clone(),
+                    description: self.description.clone(),
+                    rules: self.rules.clone(),
+                    player_counts: G::player_counts(),
+                    duration: self.duration,
+                    version: self.version.clone(),
+                },
+            }),
             Request::Status { ref game } => {
-                let game = serde_json::from_str(&game).unwrap();
-                Ok(handle_status::<G>(&game))
+                handle_status(&self.parse_game(game)?, &self.format)
             }
             Request::Play {
                 player,
                 ref command,
                 ref names,
                 ref game,
-            } => {
-                let mut game = serde_json::from_str(&game).unwrap();
-                Ok(handle_play::<G>(player, &command, &names, &mut game))
-            }
-            Request::PubRender { ref game } => {
-                let game = serde_json::from_str(&game).unwrap();
-                Ok(handle_pub_render::<G>(&game))
+                diff: false,
+            } => handle_play(
+                player,
+                &command,
+                &names,
+                &mut self.parse_game(game)?,
+                &self.format,
+            ),
+            Request::Play {
+                player,
+                ref command,
+                ref names,
+                ref game,
+                diff: true,
+            } => handle_play_diff(
+                player,
+                &command,
+                &names,
+                game,
+                &mut self.parse_game(game)?,
+                &self.format,
+            ),
+            Request::Validate {
+                player,
+                ref command,
+                ref names,
+                ref game,
+            } => Ok(handle_validate(
+                player,
+                &command,
+                &names,
+                &mut self.parse_game(game)?,
+            )),
+            Request::Suggest {
+                player,
+                ref partial_input,
+                ref names,
+                ref game,
+            } => Ok(handle_suggest(
+                player,
+                &partial_input,
+                &names,
+                &self.parse_game(game)?,
+            )),
+            Request::PubRender {
+                ref game,
+                format,
+                width,
+                ref names,
+            } => handle_pub_render(&self.parse_game(game)?, format, width, names),
+            Request::PlayerRender {
+                player,
+                ref game,
+                format,
+                width,
+                ref names,
+            } => handle_player_render(player, &self.parse_game(game)?, format, width, names),
+            Request::Logs { ref logs, player } => Ok(Response::Logs {
+                logs: CliLog::filter_for(logs, player),
+            }),
+            Request::Migrate {
+                ref game,
+                from_version,
+            } => self.handle_migrate(game, from_version),
+            Request::Batch {
+                ref requests,
+                chain,
+            } => self.handle_batch(requests, chain),
+        }
+    }
+
+    fn handle_batch(&self, requests: &[Request], chain: bool) -> Result<Response, Error> {
+        let mut responses: Vec<Response> = vec![];
+        // The game state from the previous response when chaining.
+        let mut chained_state: Option<String> = None;
+        for req in requests {
+            let mut req = req.clone();
+            if let Some(state) = chained_state {
+                if let Request::Play { ref mut game, .. } = req {
+                    *game = state;
+                }
             }
-            Request::PlayerRender { player, ref game } => {
-                let game = serde_json::from_str(&game).unwrap();
-                Ok(handle_player_render::<G>(player, &game))
+            let resp = self.handle(&req).unwrap_or_else(error_response);
+            chained_state = if chain {
+                self.chained_state(&req, &resp)?
+            } else {
+                None
+            };
+            responses.push(resp);
+            if chain && chained_state.is_none() {
+                break;
             }
         }
+        Ok(Response::Batch { responses })
+    }
+
+    /// The game state a chained batch continues from after `resp`, a `Response::PlayDiff` is
+    /// applied to the game sent with the request.
+    fn chained_state(&self, req: &Request, resp: &Response) -> Result<Option<String>, Error> {
+        Ok(match (req, resp) {
+            (&Request::Play { ref game, .. }, &Response::PlayDiff { ref patch, .. }) => {
+                let mut from = snapshot(&self.parse_game(game)?, &self.format)?;
+                from.game.state = game.to_owned();
+                Some(from.apply(patch)?.game.state)
+            }
+            _ => resp.game_state().map(str::to_owned),
+        })
+    }
+}
+
+impl<G: Gamer + Debug + Clone + Serialize + DeserializeOwned> Requester for GameRequester<G> {
+    /// Never returns an error, failures including panics inside the game are converted to
+    /// `Response::SystemError`.
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        let handled = panic::catch_unwind(AssertUnwindSafe(|| self.handle(req)))
+            .unwrap_or_else(|payload| {
+                Err(format_err!(
+                    "game panicked handling {} request: {}",
+                    req.name(),
+                    panic_message(&*payload)
+                ))
+            });
+        Ok(handled.unwrap_or_else(error_response))
+    }
+}
+
+fn error_response(e: Error) -> Response {
+    match e.downcast::<StateVersionError>() {
+        Ok(e) => Response::StateVersionError {
+            state_version: e.state_version,
+            current_version: e.current_version,
+            message: e.message,
+        },
+        Err(e) => Response::SystemError {
+            message: e.to_string(),
+        },
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.to_owned()
+    } else {
+        "unknown panic".to_string()
     }
 }
 
@@ -59,60 +560,85 @@ fn handle_player_counts<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>
 
 pub fn renders<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
     game: &G,
-) -> (PubRender, Vec<PlayerRender>) {
+) -> Result<(PubRender, Vec<PlayerRender>), Error> {
+    let pub_render = pub_render(game)?;
+    let player_renders = (0..game.player_count())
+        .map(|p| player_render(game, p))
+        .collect::<Result<Vec<PlayerRender>, Error>>()?;
+    Ok((pub_render, player_renders))
+}
+
+fn pub_render<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    game: &G,
+) -> Result<PubRender, Error> {
+    pub_render_as(game, RenderFormat::Markup, None, &[])
+}
+
+fn player_render<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    game: &G,
+    player: usize,
+) -> Result<PlayerRender, Error> {
+    player_render_as(game, player, RenderFormat::Markup, None, &[])
+}
+
+fn pub_render_as<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    game: &G,
+    format: RenderFormat,
+    width: Option<usize>,
+    names: &[String],
+) -> Result<PubRender, Error> {
     let pub_state = game.pub_state();
-    let pub_render = PubRender {
-        pub_state: serde_json::to_string(&pub_state).unwrap(),
-        render: brdgme_markup::to_string(&pub_state.render()),
-    };
-    let player_renders: Vec<PlayerRender> = (0..game.player_count())
-        .map(|p| {
-            let player_state = game.player_state(p);
-            PlayerRender {
-                player_state: serde_json::to_string(&player_state).unwrap(),
-                render: brdgme_markup::to_string(&player_state.render()),
-                command_spec: game.command_spec(p),
-            }
-        })
-        .collect();
-    (pub_render, player_renders)
-}
-
-fn handle_new<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(players: usize) -> Response {
-    match G::new(players) {
-        Ok((game, logs)) => GameResponse::from_gamer(&game)
-            .map(|gs| {
-                let (public_render, player_renders) = renders(&game);
-                Response::New {
-                    game: gs,
-                    logs: CliLog::from_logs(&logs),
-                    public_render,
-                    player_renders,
-                }
-            })
-            .unwrap_or_else(|e| Response::SystemError {
-                message: e.to_string(),
-            }),
-        Err(GameError::Internal { message }) => Response::SystemError { message },
-        Err(e) => Response::UserError {
-            message: e.to_string(),
-        },
-    }
+    Ok(PubRender {
+        pub_state: serde_json::to_string(&pub_state)
+            .map_err(|e| format_err!("unable to encode public state: {}", e))?,
+        render: render::render(
+            &pub_state.render(),
+            format,
+            &render::players(names, game.player_count()),
+            width,
+        ),
+    })
 }
 
-fn handle_status<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(game: &G) -> Response {
-    GameResponse::from_gamer(game)
-        .map(|gr| {
-            let (public_render, player_renders) = renders(game);
-            Response::Status {
-                game: gr,
-                public_render,
-                player_renders,
-            }
-        })
-        .unwrap_or_else(|e| Response::SystemError {
-            message: e.to_string(),
-        })
+fn player_render_as<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    game: &G,
+    player: usize,
+    format: RenderFormat,
+    width: Option<usize>,
+    names: &[String],
+) -> Result<PlayerRender, Error> {
+    let player_state = game.player_state(player);
+    Ok(PlayerRender {
+        player_state: serde_json::to_string(&player_state)
+            .map_err(|e| format_err!("unable to encode player state: {}", e))?,
+        render: render::render(
+            &player_state.render(),
+            format,
+            &render::players(names, game.player_count()),
+            width,
+        ),
+        command_spec: game.command_spec(player),
+    })
+}
+
+/// Generated seeds stay below `api::MAX_SEED` so JavaScript clients can send them back exactly.
+fn random_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (now.as_secs() ^ u64::from(now.subsec_nanos()).rotate_left(32)) & api::MAX_SEED
+}
+
+fn handle_status<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    game: &G,
+    format: &StateFormat,
+) -> Result<Response, Error> {
+    let (public_render, player_renders) = renders(game)?;
+    Ok(Response::Status {
+        game: GameResponse::from_gamer_with(game, format)?,
+        public_render,
+        player_renders,
+    })
 }
 
 fn handle_play<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
@@ -120,56 +646,125 @@ fn handle_play<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
     command: &str,
     names: &[String],
     game: &mut G,
-) -> Response {
+    format: &StateFormat,
+) -> Result<Response, Error> {
     match game.command(player, command, names) {
         Ok(CommandResponse {
             logs,
             can_undo,
             remaining_input,
-        }) => GameResponse::from_gamer(game)
-            .map(|gr| {
-                let (public_render, player_renders) = renders(game);
-                Response::Play {
-                    game: gr,
-                    logs: CliLog::from_logs(&logs),
-                    can_undo,
-                    remaining_input,
-                    public_render,
-                    player_renders,
-                }
+        }) => {
+            let (public_render, player_renders) = renders(game)?;
+            Ok(Response::Play {
+                game: GameResponse::from_gamer_with(game, format)?,
+                logs: CliLog::from_logs(&logs),
+                can_undo,
+                remaining_input,
+                public_render,
+                player_renders,
             })
-            .unwrap_or_else(|e| Response::SystemError {
-                message: e.to_string(),
-            }),
-        Err(GameError::Internal { message }) => Response::SystemError { message },
-        Err(e) => Response::UserError {
-            message: e.to_string(),
-        },
+        }
+        Err(e) => Ok(Response::from_game_error(e, command)),
     }
 }
 
-fn handle_pub_render<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+fn handle_play_diff<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    player: usize,
+    command: &str,
+    names: &[String],
+    state: &str,
+    game: &mut G,
+    format: &StateFormat,
+) -> Result<Response, Error> {
+    let mut from = snapshot(game, format)?;
+    // Diff against the state the client sent rather than our reencoding of it.
+    from.game.state = state.to_owned();
+    match game.command(player, command, names) {
+        Ok(CommandResponse {
+            logs,
+            can_undo,
+            remaining_input,
+        }) => Ok(Response::PlayDiff {
+            patch: from.diff(&snapshot(game, format)?)?,
+            logs: CliLog::from_logs(&logs),
+            can_undo,
+            remaining_input,
+        }),
+        Err(e) => Ok(Response::from_game_error(e, command)),
+    }
+}
+
+fn snapshot<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
     game: &G,
+    format: &StateFormat,
+) -> Result<Snapshot, Error> {
+    let (public_render, player_renders) = renders(game)?;
+    Ok(Snapshot {
+        game: GameResponse::from_gamer_with(game, format)?,
+        public_render,
+        player_renders,
+    })
+}
+
+fn handle_validate<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    player: usize,
+    command: &str,
+    names: &[String],
+    game: &mut G,
 ) -> Response {
-    let pub_state = game.pub_state();
-    Response::PubRender {
-        render: PubRender {
-            pub_state: serde_json::to_string(&pub_state).unwrap(),
-            render: brdgme_markup::to_string(&pub_state.render()),
-        },
+    match game.command(player, command, names) {
+        Ok(CommandResponse {
+            logs,
+            can_undo,
+            remaining_input,
+        }) => {
+            let parsed_len = command.len().saturating_sub(remaining_input.len());
+            Response::Validate {
+                parsed: command.get(..parsed_len).unwrap_or_default().to_string(),
+                remaining_input,
+                logs: CliLog::from_logs(&logs),
+                can_undo,
+            }
+        }
+        Err(e) => Response::from_game_error(e, command),
     }
 }
 
-fn handle_player_render<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+fn handle_suggest<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
     player: usize,
+    partial_input: &str,
+    names: &[String],
     game: &G,
 ) -> Response {
-    let player_state = game.player_state(player);
-    Response::PlayerRender {
-        render: PlayerRender {
-            player_state: serde_json::to_string(&player_state).unwrap(),
-            render: brdgme_markup::to_string(&player_state.render()),
-            command_spec: game.command_spec(player),
-        },
+    let (suggestions, complete) = game
+        .command_spec(player)
+        .map(|spec| suggest::suggest(&spec, partial_input, names))
+        .unwrap_or_default();
+    Response::Suggest {
+        suggestions,
+        complete,
     }
 }
+
+fn handle_pub_render<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    game: &G,
+    format: RenderFormat,
+    width: Option<usize>,
+    names: &[String],
+) -> Result<Response, Error> {
+    Ok(Response::PubRender {
+        render: pub_render_as(game, format, width, names)?,
+    })
+}
+
+fn handle_player_render<G: Gamer + Debug + Clone + Serialize + DeserializeOwned>(
+    player: usize,
+    game: &G,
+    format: RenderFormat,
+    width: Option<usize>,
+    names: &[String],
+) -> Result<Response, Error> {
+    Ok(Response::PlayerRender {
+        render: player_render_as(game, player, format, width, names)?,
+    })
+}
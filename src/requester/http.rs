@@ -0,0 +1,77 @@
+use failure::{bail, format_err, Error};
+use serde_json;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use crate::api::{Request, Response};
+use crate::http::{read_headers, REQUEST_PATH};
+use crate::requester::Requester;
+
+/// Sends requests to a game server started with `http::serve`.
+pub struct HttpRequester {
+    host: String,
+    path: String,
+}
+
+impl HttpRequester {
+    /// Creates a requester for a URL such as `http://localhost:8000`, any path in the URL is used
+    /// as a prefix for the request path.
+    pub fn new(url: &str) -> Result<Self, Error> {
+        let url = match url.find("://") {
+            Some(i) if &url[..i] == "http" => &url[i + 3..],
+            Some(_) => bail!("only http URLs are supported"),
+            None => url,
+        };
+        let (host, prefix) = match url.find('/') {
+            Some(i) => url.split_at(i),
+            None => (url, ""),
+        };
+        if host.is_empty() {
+            bail!("expected a host in the URL");
+        }
+        Ok(HttpRequester {
+            host: host.to_owned(),
+            path: format!("{}{}", prefix.trim_end_matches('/'), REQUEST_PATH),
+        })
+    }
+}
+
+impl Requester for HttpRequester {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        let addr = if self.host.contains(':') {
+            self.host.clone()
+        } else {
+            format!("{}:80", self.host)
+        };
+        let mut stream = TcpStream::connect(addr)?;
+        let body = serde_json::to_vec(req)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let content_length = read_headers(&mut reader)?;
+        let mut resp = vec![0; content_length];
+        reader.read_exact(&mut resp)?;
+
+        // Error statuses still carry a `Response::SystemError` body, so only fail if it can't be
+        // parsed.
+        serde_json::from_slice(&resp).map_err(|e| {
+            format_err!(
+                "failed to parse JSON: {}\n\nHTTP status:\n{}\n\nHTTP body:\n{}\n\n",
+                e,
+                status_line.trim(),
+                String::from_utf8_lossy(&resp)
+            )
+        })
+    }
+}
@@ -1,50 +1,598 @@
-use failure::{Error, format_err};
-use serde_json;
+use failure::{bail, format_err, Error, Fail};
 
 use std::ffi::OsString;
-use std::io::{BufWriter, Write};
-use std::process::{Command, Stdio};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use crate::api::{Request, Response};
+use crate::cli::SERVE_ARG;
+use crate::encoding::{self, Codec, Compression, Encoding};
 use crate::requester::Requester;
 
+/// How often a child with a timeout or output limit is checked for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Returned when a child process doesn't respond within the configured timeout, the child is killed
+/// before this is returned.
+#[derive(Debug, Fail)]
+#[fail(
+    display = "child process timed out after {:?}\n\nChild process stderr:\n{}\n\n",
+    timeout, stderr
+)]
+pub struct TimeoutError {
+    pub timeout: Duration,
+    /// Everything the child wrote to stderr before it was killed.
+    pub stderr: String,
+}
+
+/// Returned when a child process exceeds one of its `Limits`. Memory, CPU and open file limits
+/// are enforced by the OS, so they are detected from how the child died. Detection is best effort,
+/// see `Limits::exceeded`.
+#[derive(Debug, Fail)]
+pub enum LimitError {
+    #[fail(
+        display = "child process exceeded memory limit of {} bytes\n\nChild process stderr:\n{}\n\n",
+        limit, stderr
+    )]
+    Memory { limit: u64, stderr: String },
+    #[fail(
+        display = "child process exceeded CPU time limit of {} seconds\n\nChild process stderr:\n{}\n\n",
+        limit, stderr
+    )]
+    CpuTime { limit: u64, stderr: String },
+    #[fail(
+        display = "child process exceeded open file limit of {}\n\nChild process stderr:\n{}\n\n",
+        limit, stderr
+    )]
+    OpenFiles { limit: u64, stderr: String },
+    #[fail(
+        display = "child process exceeded output limit of {} bytes\n\nChild process stderr:\n{}\n\n",
+        limit, stderr
+    )]
+    Output { limit: usize, stderr: String },
+}
+
+/// Resource limits applied to spawned game processes, `None` leaves a resource unlimited.
+/// Persistent children share their CPU time limit across all requests.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Maximum address space in bytes.
+    pub memory: Option<u64>,
+    /// Maximum CPU time in seconds.
+    pub cpu_time: Option<u64>,
+    pub open_files: Option<u64>,
+    /// Maximum bytes read from stdout for a single response, the child is killed if exceeded.
+    pub output: Option<usize>,
+    /// Maximum bytes of stderr kept, older output is discarded. Uses `output` if not set.
+    pub stderr: Option<usize>,
+}
+
+impl Limits {
+    #[cfg(unix)]
+    fn apply(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let limits = self.clone();
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(memory) = limits.memory {
+                    set_rlimit(libc::RLIMIT_AS, memory, memory)?;
+                }
+                if let Some(cpu_time) = limits.cpu_time {
+                    // The hard limit is higher so the child gets SIGXCPU before SIGKILL.
+                    set_rlimit(libc::RLIMIT_CPU, cpu_time, cpu_time + 1)?;
+                }
+                if let Some(open_files) = limits.open_files {
+                    set_rlimit(libc::RLIMIT_NOFILE, open_files, open_files)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn apply(&self, _cmd: &mut Command) {}
+
+    fn stderr_limit(&self) -> Option<usize> {
+        self.stderr.or(self.output)
+    }
+
+    /// Works out whether a dead child was killed for exceeding a limit. CPU time is detected from
+    /// the exit signal, but memory and open file limits only make allocations and opens fail, so
+    /// they are detected from the error the child writes to stderr. A child which handles those
+    /// errors itself and exits cleanly isn't reported.
+    fn exceeded(&self, status: Option<ExitStatus>, stderr: &str) -> Option<LimitError> {
+        let stderr = stderr.to_owned();
+        if let Some(limit) = self.cpu_time {
+            if status.map_or(false, cpu_time_exceeded) {
+                return Some(LimitError::CpuTime { limit, stderr });
+            }
+        }
+        if let Some(limit) = self.memory {
+            if stderr.contains("memory allocation of") || stderr.contains("out of memory") {
+                return Some(LimitError::Memory { limit, stderr });
+            }
+        }
+        if let Some(limit) = self.open_files {
+            if stderr.contains("Too many open files") {
+                return Some(LimitError::OpenFiles { limit, stderr });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+#[cfg(unix)]
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64) -> io::Result<()> {
+    let rlim = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(resource, &rlim) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn cpu_time_exceeded(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal() == Some(libc::SIGXCPU)
+}
+
+#[cfg(not(unix))]
+fn cpu_time_exceeded(_status: ExitStatus) -> bool {
+    false
+}
+
 pub struct LocalRequester {
     path: OsString,
+    persistent: bool,
+    timeout: Option<Duration>,
+    limits: Limits,
+    /// The preferred codec, which is negotiated with the child before it's first used.
+    codec: Codec,
+    negotiated: Option<Codec>,
+    server: Option<Server>,
 }
 
 impl LocalRequester {
+    /// Creates a requester which spawns a new process for each request.
     pub fn new<I: Into<OsString>>(path: I) -> Self {
-        LocalRequester { path: path.into() }
+        LocalRequester {
+            path: path.into(),
+            persistent: false,
+            timeout: None,
+            limits: Limits::default(),
+            codec: Codec::default(),
+            negotiated: None,
+            server: None,
+        }
+    }
+
+    /// Creates a requester which spawns a single process running `cli::serve` and reuses it for
+    /// each request, respawning it if it dies.
+    pub fn persistent<I: Into<OsString>>(path: I) -> Self {
+        LocalRequester {
+            persistent: true,
+            ..LocalRequester::new(path)
+        }
+    }
+
+    /// Kills the child and returns a `TimeoutError` if a request takes longer than `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Applies resource limits to spawned children.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Prefers `encoding` for requests and responses if the child supports it.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.codec.encoding = encoding;
+        self.negotiated = None;
+        self
+    }
+
+    /// Asks the child to compress game states in responses if it supports it, compressed states
+    /// can be sent back as is and are decompressed by the child. Use `encoding::decompress_state`
+    /// to read them.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.codec.compression = Some(compression);
+        self.negotiated = None;
+        self
+    }
+
+    /// Works out which parts of the preferred codec the child supports using a JSON
+    /// `Request::Hello`.
+    fn negotiate(&mut self) -> Result<Codec, Error> {
+        if self.codec.is_default() {
+            return Ok(self.codec);
+        }
+        if let Some(codec) = self.negotiated {
+            return Ok(codec);
+        }
+        let capabilities = match self.request_once(&Request::Hello, Codec::default())? {
+            Response::Hello { hello } => hello.capabilities,
+            // Games built before `Request::Hello` only support JSON.
+            _ => vec![],
+        };
+        let codec = self.codec.negotiate(&capabilities);
+        self.negotiated = Some(codec);
+        Ok(codec)
+    }
+
+    fn request_once(&self, req: &Request, codec: Codec) -> Result<Response, Error> {
+        let mut cmd = Command::new(&self.path);
+        cmd.args(codec.args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        self.limits.apply(&mut cmd);
+        let mut child = cmd.spawn()?;
+
+        let input = codec.encoding.encode(req)?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or(format_err!("failed to get stdin"))?;
+        // Written on a separate thread so a child which never reads can't block us past the
+        // timeout, stdin is closed when the thread finishes.
+        thread::spawn(move || stdin.write_all(&input));
+        let stdout = Captured::spawn(
+            child
+                .stdout
+                .take()
+                .ok_or(format_err!("failed to get stdout"))?,
+            self.limits.output,
+        );
+        let stderr = Captured::tail(
+            child
+                .stderr
+                .take()
+                .ok_or(format_err!("failed to get stderr"))?,
+            self.limits.stderr_limit(),
+        );
+
+        let status = if self.timeout.is_none() && self.limits.output.is_none() {
+            child.wait()?
+        } else {
+            let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    break status;
+                }
+                if let Some(limit) = self.limits.output.filter(|_| stdout.exceeded()) {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(LimitError::Output {
+                        limit,
+                        stderr: stderr.to_string_lossy(),
+                    }.into());
+                }
+                if let (Some(timeout), Some(deadline)) = (self.timeout, deadline) {
+                    if Instant::now() >= deadline {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(TimeoutError {
+                            timeout,
+                            stderr: stderr.to_string_lossy(),
+                        }.into());
+                    }
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        };
+
+        let exceeded = stdout.exceeded();
+        let stdout = stdout.finish();
+        let stderr = stderr.finish();
+        if let Some(limit) = self.limits.output.filter(|_| exceeded) {
+            return Err(LimitError::Output {
+                limit,
+                stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            }.into());
+        }
+        if !status.success() {
+            if let Some(e) = self
+                .limits
+                .exceeded(Some(status), &String::from_utf8_lossy(&stderr))
+            {
+                return Err(e.into());
+            }
+        }
+        parse_response(&stdout, &stderr, codec.encoding)
+    }
+
+    fn request_persistent(&mut self, req: &Request, codec: Codec) -> Result<Response, Error> {
+        if self.server.is_none() {
+            self.server = Some(Server::spawn(&self.path, &self.limits, codec)?);
+        }
+        let result = match self.server {
+            Some(ref mut server) => server.request(req, self.timeout, &self.limits),
+            None => unreachable!(),
+        };
+        if result.is_err() {
+            // The child is in an unknown state, so start fresh on the next request.
+            self.server = None;
+        }
+        result
     }
 }
 
 impl Requester for LocalRequester {
     fn request(&mut self, req: &Request) -> Result<Response, Error> {
-        let mut cmd = Command::new(&self.path)
+        let codec = self.negotiate()?;
+        if self.persistent {
+            self.request_persistent(req, codec)
+        } else {
+            self.request_once(req, codec)
+        }
+    }
+}
+
+/// Output from a child pipe, read on a separate thread so a chatty child can't block on a full
+/// pipe.
+struct Captured {
+    buf: Arc<Mutex<Vec<u8>>>,
+    exceeded: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Captured {
+    /// Reading stops once `limit` bytes is exceeded, closing the pipe.
+    fn spawn<R: Read + Send + 'static>(r: R, limit: Option<usize>) -> Self {
+        Captured::spawn_with(r, limit, false)
+    }
+
+    /// Reads until the pipe closes, only keeping the last `limit` bytes.
+    fn tail<R: Read + Send + 'static>(r: R, limit: Option<usize>) -> Self {
+        Captured::spawn_with(r, limit, true)
+    }
+
+    fn spawn_with<R: Read + Send + 'static>(mut r: R, limit: Option<usize>, tail: bool) -> Self {
+        let buf = Arc::new(Mutex::new(vec![]));
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let thread_buf = Arc::clone(&buf);
+        let thread_exceeded = Arc::clone(&exceeded);
+        let handle = thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            while let Ok(n) = r.read(&mut chunk) {
+                if n == 0 {
+                    break;
+                }
+                if let Ok(mut buf) = thread_buf.lock() {
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(limit) = limit.filter(|&limit| buf.len() > limit) {
+                        if tail {
+                            let excess = buf.len() - limit;
+                            buf.drain(..excess);
+                        } else {
+                            thread_exceeded.store(true, Ordering::SeqCst);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Captured {
+            buf,
+            exceeded,
+            handle,
+        }
+    }
+
+    fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::SeqCst)
+    }
+
+    /// Everything read so far.
+    fn to_string_lossy(&self) -> String {
+        self.buf
+            .lock()
+            .map(|buf| String::from_utf8_lossy(&buf).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Discards everything read so far.
+    fn clear(&self) {
+        if let Ok(mut buf) = self.buf.lock() {
+            buf.clear();
+        }
+    }
+
+    /// Waits for the pipe to close and returns everything read.
+    fn finish(self) -> Vec<u8> {
+        let _ = self.handle.join();
+        self.buf
+            .lock()
+            .map(|mut buf| buf.split_off(0))
+            .unwrap_or_default()
+    }
+}
+
+/// A long running child process started with `SERVE_ARG`.
+struct Server {
+    child: Child,
+    stdin: ChildStdin,
+    /// Response messages, `None` is sent if a message exceeds the output limit.
+    messages: Receiver<Option<Vec<u8>>>,
+    stderr: Captured,
+    encoding: Encoding,
+}
+
+impl Server {
+    fn spawn(path: &OsString, limits: &Limits, codec: Codec) -> Result<Self, Error> {
+        let mut cmd = Command::new(path);
+        cmd.arg(SERVE_ARG)
+            .args(codec.args())
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            .stderr(Stdio::piped());
+        limits.apply(&mut cmd);
+        let mut child = cmd.spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or(format_err!("failed to get stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or(format_err!("failed to get stdout"))?;
+        let stderr = Captured::tail(
+            child
+                .stderr
+                .take()
+                .ok_or(format_err!("failed to get stderr"))?,
+            limits.stderr_limit(),
+        );
+        // Messages are read on a separate thread so waiting for a response can time out.
+        let (tx, messages) = mpsc::channel();
+        let limit = limits.output;
+        let encoding = codec.encoding;
+        thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            loop {
+                let message = if encoding.is_binary() {
+                    read_binary_message(&mut stdout, limit)
+                } else {
+                    read_line_message(&mut stdout, limit)
+                };
+                match message {
+                    Ok(Some(message)) => {
+                        let exceeded = message.is_none();
+                        if tx.send(message).is_err() || exceeded {
+                            break;
+                        }
+                    }
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        });
+        Ok(Server {
+            child,
+            stdin,
+            messages,
+            stderr,
+            encoding,
+        })
+    }
 
-        {
-            let mut wr = cmd.stdin
-                .as_mut()
-                .ok_or(format_err!("failed to get stdin"))?;
-            let mut bufwr = BufWriter::new(&mut wr);
+    fn request(
+        &mut self,
+        req: &Request,
+        timeout: Option<Duration>,
+        limits: &Limits,
+    ) -> Result<Response, Error> {
+        let message = self.encoding.encode(req)?;
+        // Errors only include stderr written while handling this request.
+        self.stderr.clear();
+        self.encoding.write_message(&mut self.stdin, &message)?;
 
-            bufwr.write_all(serde_json::to_string(req)?.as_bytes())?;
-            bufwr.flush()?;
+        let resp = match timeout {
+            None => self.messages.recv().ok(),
+            Some(timeout) => match self.messages.recv_timeout(timeout) {
+                Ok(resp) => Some(resp),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(TimeoutError {
+                        timeout,
+                        stderr: self.stderr.to_string_lossy(),
+                    }.into())
+                }
+                Err(RecvTimeoutError::Disconnected) => None,
+            },
+        };
+        match resp {
+            Some(Some(resp)) => parse_response(
+                &resp,
+                self.stderr.to_string_lossy().as_bytes(),
+                self.encoding,
+            ),
+            Some(None) => Err(LimitError::Output {
+                limit: limits.output.unwrap_or_default(),
+                stderr: self.stderr.to_string_lossy(),
+            }.into()),
+            None => {
+                // Give the child a moment to finish dying so its exit status is available.
+                thread::sleep(POLL_INTERVAL);
+                let status = self.child.try_wait().ok().and_then(|status| status);
+                let stderr = self.stderr.to_string_lossy();
+                match limits.exceeded(status, &stderr) {
+                    Some(e) => Err(e.into()),
+                    None => bail!(
+                        "child process closed stdout\n\nChild process stderr:\n{}\n\n",
+                        stderr
+                    ),
+                }
+            }
         }
+    }
+}
 
-        let output = cmd.wait_with_output()?;
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
 
-        serde_json::from_slice(&output.stdout).map_err(|e| {
-            format_err!(
-                "failed to parse JSON: {}\n\nChild process stderr:\n{}\n\nChild process stdout:\n{}\n\n",
-                e,
-                String::from_utf8_lossy(&output.stderr),
-                String::from_utf8_lossy(&output.stdout)
-            )
-        })
+/// Reads a newline delimited message, the inner `None` means it exceeded `limit`.
+fn read_line_message<R: BufRead>(
+    r: &mut R,
+    limit: Option<usize>,
+) -> io::Result<Option<Option<Vec<u8>>>> {
+    let mut line = vec![];
+    let read = match limit {
+        Some(limit) => r.take(limit as u64 + 1).read_until(b'\n', &mut line)?,
+        None => r.read_until(b'\n', &mut line)?,
+    };
+    Ok(match read {
+        0 => None,
+        _ if !line.ends_with(b"\n") && limit.map_or(false, |l| line.len() > l) => Some(None),
+        _ => Some(Some(line)),
+    })
+}
+
+/// Reads a length prefixed message, the inner `None` means it exceeded `limit`.
+fn read_binary_message<R: Read>(
+    r: &mut R,
+    limit: Option<usize>,
+) -> io::Result<Option<Option<Vec<u8>>>> {
+    let len = match encoding::read_message_len(r)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    if limit.map_or(false, |l| len > l) {
+        return Ok(Some(None));
     }
+    let mut message = vec![0; len];
+    r.read_exact(&mut message)?;
+    Ok(Some(Some(message)))
+}
+
+fn parse_response(stdout: &[u8], stderr: &[u8], encoding: Encoding) -> Result<Response, Error> {
+    encoding.decode(stdout).map_err(|e| {
+        format_err!(
+            "failed to parse {}: {}\n\nChild process stderr:\n{}\n\nChild process stdout:\n{}\n\n",
+            encoding.name(),
+            e,
+            String::from_utf8_lossy(stderr),
+            String::from_utf8_lossy(stdout)
+        )
+    })
 }
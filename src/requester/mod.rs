@@ -1,26 +1,86 @@
-use failure::{bail, Error};
+use failure::{bail, format_err, Error};
 
-use crate::api::{Request, Response};
+use std::sync::Mutex;
+
+use crate::api::{Hello, Request, Response};
 
 pub mod gamer;
+pub mod http;
 pub mod local;
+pub mod socket;
 
 pub trait Requester {
     fn request(&mut self, req: &Request) -> Result<Response, Error>;
 }
 
-pub fn parse_args(args: &[String]) -> Result<impl Requester, Error> {
+impl<R: Requester + ?Sized> Requester for Box<R> {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        (**self).request(req)
+    }
+}
+
+impl<'a, R: Requester + ?Sized> Requester for &'a mut R {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        (**self).request(req)
+    }
+}
+
+/// Lets threads share a requester, requests are handled one at a time.
+impl<'a, R: Requester + ?Sized> Requester for &'a Mutex<R> {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        self.lock()
+            .map_err(|_| format_err!("requester lock poisoned"))?
+            .request(req)
+    }
+}
+
+/// Performs a handshake with the requester, treating a `SystemError` as a game built before
+/// `Request::Hello` existed.
+pub fn hello<R: Requester>(requester: &mut R) -> Result<Hello, Error> {
+    match requester.request(&Request::Hello)? {
+        Response::Hello { hello } => Ok(hello),
+        Response::SystemError { .. } => Ok(Hello::legacy()),
+        _ => bail!("unexpected response to hello request"),
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Box<dyn Requester>, Error> {
     let args_len = args.len();
     if args_len < 2 {
-        bail!("expected a type argument of 'local'");
+        bail!("expected a type argument of 'local', 'serve', 'tcp', 'unix' or 'http'");
     }
     Ok(match args[1].as_ref() {
         "local" => {
             if args_len < 3 {
                 bail!("expected a path argument");
             }
-            local::LocalRequester::new(&args[2])
+            Box::new(local::LocalRequester::new(&args[2]))
+        }
+        "serve" => {
+            if args_len < 3 {
+                bail!("expected a path argument");
+            }
+            Box::new(local::LocalRequester::persistent(&args[2]))
+        }
+        "tcp" => {
+            if args_len < 3 {
+                bail!("expected an address argument");
+            }
+            Box::new(socket::SocketRequester::tcp(args[2].as_str()))
+        }
+        #[cfg(unix)]
+        "unix" => {
+            if args_len < 3 {
+                bail!("expected a path argument");
+            }
+            Box::new(socket::SocketRequester::unix(&args[2]))
+        }
+        "http" => {
+            if args_len < 3 {
+                bail!("expected a URL argument");
+            }
+            Box::new(http::HttpRequester::new(&args[2])?)
         }
-        _ => panic!("expected one of 'local'"),
+        _ => panic!("expected one of 'local', 'serve', 'tcp', 'unix', 'http'"),
     })
 }
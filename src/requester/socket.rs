@@ -0,0 +1,97 @@
+use failure::{bail, Error};
+use serde_json;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use crate::api::{Request, Response};
+use crate::requester::Requester;
+
+pub enum Addr {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Sends newline delimited requests to a game server started with `cli::serve_tcp` or
+/// `cli::serve_unix`. The connection is kept open between requests and reopened after an error.
+pub struct SocketRequester {
+    addr: Addr,
+    conn: Option<Conn>,
+}
+
+impl SocketRequester {
+    pub fn tcp<A: Into<String>>(addr: A) -> Self {
+        SocketRequester {
+            addr: Addr::Tcp(addr.into()),
+            conn: None,
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn unix<P: Into<PathBuf>>(path: P) -> Self {
+        SocketRequester {
+            addr: Addr::Unix(path.into()),
+            conn: None,
+        }
+    }
+}
+
+impl Requester for SocketRequester {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        if self.conn.is_none() {
+            self.conn = Some(Conn::connect(&self.addr)?);
+        }
+        let result = match self.conn {
+            Some(ref mut conn) => conn.request(req),
+            None => unreachable!(),
+        };
+        if result.is_err() {
+            self.conn = None;
+        }
+        result
+    }
+}
+
+struct Conn {
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: Box<dyn Write + Send>,
+}
+
+impl Conn {
+    fn connect(addr: &Addr) -> Result<Self, Error> {
+        let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = match *addr {
+            Addr::Tcp(ref addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                (Box::new(stream.try_clone()?), Box::new(stream))
+            }
+            #[cfg(unix)]
+            Addr::Unix(ref path) => {
+                let stream = UnixStream::connect(path)?;
+                (Box::new(stream.try_clone()?), Box::new(stream))
+            }
+        };
+        Ok(Conn {
+            reader: BufReader::new(reader),
+            writer,
+        })
+    }
+
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        let mut line = serde_json::to_string(req)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()?;
+
+        let mut resp = String::new();
+        if self.reader.read_line(&mut resp)? == 0 {
+            bail!("server closed the connection");
+        }
+        Ok(serde_json::from_str(&resp)?)
+    }
+}
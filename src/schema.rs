@@ -0,0 +1,755 @@
+use serde_json::{json, Map, Value};
+
+/// The serialised shape of a value, following serde's default representations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Bool,
+    Integer,
+    Unsigned,
+    Number,
+    String,
+    /// Any JSON value.
+    Any,
+    /// `null` or the inner shape.
+    Option(Box<Shape>),
+    Array(Box<Shape>),
+    /// An object with any string keys, each value having the inner shape.
+    Map(Box<Shape>),
+    /// A named `Definition`.
+    Ref(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub shape: Shape,
+    /// Optional fields are `Option` or `#[serde(default)]` and may be omitted when deserialising.
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fields {
+    pub fields: Vec<Field>,
+}
+
+/// The content of an enum variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variant {
+    /// Serialises as the variant name.
+    Unit,
+    /// Serialises as an object with the variant name as the only key and the content as its value.
+    Newtype(Shape),
+    /// Serialises as an object with the variant name as the only key and the fields as its value.
+    Struct(Fields),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    Struct(Fields),
+    /// An externally tagged enum.
+    Enum(Vec<(&'static str, Variant)>),
+    Alias(Shape),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub description: Option<&'static str>,
+    pub kind: Kind,
+}
+
+fn field(name: &'static str, shape: Shape) -> Field {
+    Field {
+        name,
+        shape,
+        optional: false,
+    }
+}
+
+/// An `Option` field, or a `#[serde(default)]` field if `shape` isn't an `Option`.
+fn optional(name: &'static str, shape: Shape) -> Field {
+    Field {
+        name,
+        shape,
+        optional: true,
+    }
+}
+
+fn fields(fields: Vec<Field>) -> Variant {
+    Variant::Struct(Fields { fields })
+}
+
+fn opt(shape: Shape) -> Shape {
+    Shape::Option(Box::new(shape))
+}
+
+fn array(shape: Shape) -> Shape {
+    Shape::Array(Box::new(shape))
+}
+
+fn map(shape: Shape) -> Shape {
+    Shape::Map(Box::new(shape))
+}
+
+fn strings() -> Shape {
+    array(Shape::String)
+}
+
+fn def(name: &'static str, kind: Kind) -> Definition {
+    Definition {
+        name,
+        description: None,
+        kind,
+    }
+}
+
+fn def_struct(name: &'static str, f: Vec<Field>) -> Definition {
+    def(name, Kind::Struct(Fields { fields: f }))
+}
+
+/// Describes every type in the `api` and `bot_cli` protocols. This must be kept in sync with the
+/// serde derives of those types, `tests/schema.rs` checks serialised samples of every variant
+/// against it using `validate`.
+pub fn definitions() -> Vec<Definition> {
+    use self::Shape::*;
+    use self::Variant::{Newtype, Unit};
+
+    let renders = || {
+        vec![
+            field("public_render", Ref("PubRender")),
+            field("player_renders", array(Ref("PlayerRender"))),
+        ]
+    };
+    let render_options = || {
+        vec![
+            optional("format", Ref("RenderFormat")),
+            optional("width", opt(Unsigned)),
+            optional("names", strings()),
+        ]
+    };
+
+    vec![
+        def(
+            "Request",
+            Kind::Enum(vec![
+                ("Hello", Unit),
+                ("PlayerCounts", Unit),
+                ("Info", Unit),
+                (
+                    "New",
+                    fields(vec![
+                        field("players", Unsigned),
+                        optional("seed", opt(Unsigned)),
+                        optional("options", opt(Any)),
+                    ]),
+                ),
+                ("Options", Unit),
+                (
+                    "Replay",
+                    fields(vec![
+                        field("players", Unsigned),
+                        optional("seed", opt(Unsigned)),
+                        optional("options", opt(Any)),
+                        field("names", strings()),
+                        field("commands", array(Ref("ReplayCommand"))),
+                    ]),
+                ),
+                ("Status", fields(vec![field("game", String)])),
+                (
+                    "Play",
+                    fields(vec![
+                        field("player", Unsigned),
+                        field("command", String),
+                        field("names", strings()),
+                        field("game", String),
+                        optional("diff", Bool),
+                    ]),
+                ),
+                (
+                    "Validate",
+                    fields(vec![
+                        field("player", Unsigned),
+                        field("command", String),
+                        field("names", strings()),
+                        field("game", String),
+                    ]),
+                ),
+                (
+                    "Suggest",
+                    fields(vec![
+                        field("player", Unsigned),
+                        field("partial_input", String),
+                        optional("names", strings()),
+                        field("game", String),
+                    ]),
+                ),
+                (
+                    "PubRender",
+                    fields(
+                        vec![field("game", String)]
+                            .into_iter()
+                            .chain(render_options())
+                            .collect(),
+                    ),
+                ),
+                (
+                    "PlayerRender",
+                    fields(
+                        vec![field("player", Unsigned), field("game", String)]
+                            .into_iter()
+                            .chain(render_options())
+                            .collect(),
+                    ),
+                ),
+                (
+                    "Logs",
+                    fields(vec![
+                        field("logs", array(Ref("CliLog"))),
+                        optional("player", opt(Unsigned)),
+                    ]),
+                ),
+                (
+                    "Migrate",
+                    fields(vec![
+                        field("game", String),
+                        optional("from_version", opt(Unsigned)),
+                    ]),
+                ),
+                (
+                    "Batch",
+                    fields(vec![
+                        field("requests", array(Ref("Request"))),
+                        optional("chain", Bool),
+                    ]),
+                ),
+            ]),
+        ),
+        def_struct(
+            "ReplayCommand",
+            vec![field("player", Unsigned), field("command", String)],
+        ),
+        def(
+            "RenderFormat",
+            Kind::Enum(vec![
+                ("Markup", Unit),
+                ("Ansi", Unit),
+                ("Plain", Unit),
+                ("Html", Unit),
+                ("Svg", Unit),
+            ]),
+        ),
+        Definition {
+            description: Some("`at` is a timestamp without a timezone, eg. 2018-01-02T03:04:05"),
+            ..def_struct(
+                "CliLog",
+                vec![
+                    field("content", String),
+                    field("at", String),
+                    field("public", Bool),
+                    field("to", array(Unsigned)),
+                ],
+            )
+        },
+        def_struct(
+            "GameResponse",
+            vec![
+                field("state", String),
+                field("points", array(Number)),
+                field("status", Ref("Status")),
+                optional("options", opt(Any)),
+            ],
+        ),
+        def_struct(
+            "GameOption",
+            vec![
+                field("name", String),
+                optional("description", opt(String)),
+                field("kind", Ref("GameOptionKind")),
+            ],
+        ),
+        def(
+            "GameOptionKind",
+            Kind::Enum(vec![
+                ("Bool", fields(vec![field("default", Bool)])),
+                (
+                    "Int",
+                    fields(vec![
+                        optional("min", opt(Integer)),
+                        optional("max", opt(Integer)),
+                        field("default", Integer),
+                    ]),
+                ),
+                (
+                    "Enum",
+                    fields(vec![field("values", strings()), field("default", String)]),
+                ),
+            ]),
+        ),
+        def_struct(
+            "PubRender",
+            vec![field("pub_state", String), field("render", String)],
+        ),
+        def_struct(
+            "PlayerRender",
+            vec![
+                field("player_state", String),
+                field("render", String),
+                optional("command_spec", opt(Ref("CommandSpec"))),
+            ],
+        ),
+        def_struct(
+            "Hello",
+            vec![
+                field("protocol_version", Unsigned),
+                optional("name", opt(String)),
+                optional("version", opt(String)),
+                field("capabilities", strings()),
+            ],
+        ),
+        def_struct(
+            "Info",
+            vec![
+                optional("name", opt(String)),
+                optional("description", opt(String)),
+                optional("rules", opt(String)),
+                field("player_counts", array(Unsigned)),
+                optional("duration", opt(Unsigned)),
+                optional("version", opt(String)),
+            ],
+        ),
+        def(
+            "Response",
+            Kind::Enum(vec![
+                ("Hello", fields(vec![field("hello", Ref("Hello"))])),
+                (
+                    "PlayerCounts",
+                    fields(vec![field("player_counts", array(Unsigned))]),
+                ),
+                ("Info", fields(vec![field("info", Ref("Info"))])),
+                (
+                    "Options",
+                    fields(vec![field("options", array(Ref("GameOption")))]),
+                ),
+                (
+                    "New",
+                    fields(
+                        vec![
+                            field("game", Ref("GameResponse")),
+                            field("logs", array(Ref("CliLog"))),
+                            optional("seed", opt(Unsigned)),
+                        ].into_iter()
+                        .chain(renders())
+                        .collect(),
+                    ),
+                ),
+                (
+                    "Status",
+                    fields(
+                        vec![field("game", Ref("GameResponse"))]
+                            .into_iter()
+                            .chain(renders())
+                            .collect(),
+                    ),
+                ),
+                (
+                    "Play",
+                    fields(
+                        vec![
+                            field("game", Ref("GameResponse")),
+                            field("logs", array(Ref("CliLog"))),
+                            field("can_undo", Bool),
+                            field("remaining_input", String),
+                        ].into_iter()
+                        .chain(renders())
+                        .collect(),
+                    ),
+                ),
+                (
+                    "Replay",
+                    fields(
+                        vec![
+                            field("game", Ref("GameResponse")),
+                            field("logs", array(Ref("CliLog"))),
+                            field("command_logs", array(array(Ref("CliLog")))),
+                        ].into_iter()
+                        .chain(renders())
+                        .collect(),
+                    ),
+                ),
+                (
+                    "Validate",
+                    fields(vec![
+                        field("parsed", String),
+                        field("remaining_input", String),
+                        field("logs", array(Ref("CliLog"))),
+                        field("can_undo", Bool),
+                    ]),
+                ),
+                (
+                    "Suggest",
+                    fields(vec![
+                        field("suggestions", array(Ref("Suggestion"))),
+                        field("complete", Bool),
+                    ]),
+                ),
+                (
+                    "ReplayError",
+                    fields(vec![
+                        field("index", Unsigned),
+                        field("error", Ref("Response")),
+                    ]),
+                ),
+                ("PubRender", fields(vec![field("render", Ref("PubRender"))])),
+                (
+                    "PlayerRender",
+                    fields(vec![field("render", Ref("PlayerRender"))]),
+                ),
+                ("Logs", fields(vec![field("logs", array(Ref("CliLog")))])),
+                (
+                    "PlayDiff",
+                    fields(vec![
+                        field("patch", Ref("Patch")),
+                        field("logs", array(Ref("CliLog"))),
+                        field("can_undo", Bool),
+                        field("remaining_input", String),
+                    ]),
+                ),
+                (
+                    "Migrate",
+                    fields(
+                        vec![field("game", Ref("GameResponse"))]
+                            .into_iter()
+                            .chain(renders())
+                            .collect(),
+                    ),
+                ),
+                (
+                    "Batch",
+                    fields(vec![field("responses", array(Ref("Response")))]),
+                ),
+                (
+                    "StateVersionError",
+                    fields(vec![
+                        field("state_version", Unsigned),
+                        field("current_version", Unsigned),
+                        field("message", String),
+                    ]),
+                ),
+                (
+                    "UserError",
+                    fields(vec![
+                        field("message", String),
+                        optional("kind", opt(Ref("ErrorKind"))),
+                        optional("code", opt(Unsigned)),
+                        optional("span", opt(Ref("Span"))),
+                    ]),
+                ),
+                ("SystemError", fields(vec![field("message", String)])),
+            ]),
+        ),
+        def(
+            "ErrorKind",
+            Kind::Enum(vec![
+                ("PlayerCount", Unit),
+                ("InvalidInput", Unit),
+                ("NotYourTurn", Unit),
+                ("Finished", Unit),
+                ("Parse", Unit),
+                ("InvalidOptions", Unit),
+                ("UnexpectedInput", Unit),
+            ]),
+        ),
+        def_struct("Span", vec![field("start", Unsigned), field("end", Unsigned)]),
+        def_struct(
+            "Suggestion",
+            vec![field("token", String), field("command", String)],
+        ),
+        Definition {
+            name: "Status",
+            description: Some("The status of a game, defined by brdgme_game."),
+            kind: Kind::Enum(vec![
+                (
+                    "Active",
+                    fields(vec![
+                        field("whose_turn", array(Unsigned)),
+                        field("eliminated", array(Unsigned)),
+                    ]),
+                ),
+                (
+                    "Finished",
+                    fields(vec![
+                        field("placings", array(Unsigned)),
+                        field("stats", array(map(Any))),
+                    ]),
+                ),
+            ]),
+        },
+        Definition {
+            name: "CommandSpec",
+            description: Some("A command spec defined by brdgme_game."),
+            kind: Kind::Enum(vec![
+                ("Token", Newtype(String)),
+                (
+                    "Enum",
+                    fields(vec![field("values", strings()), field("exact", Bool)]),
+                ),
+                ("Player", Unit),
+                (
+                    "Int",
+                    fields(vec![
+                        optional("min", opt(Integer)),
+                        optional("max", opt(Integer)),
+                    ]),
+                ),
+                ("Space", Unit),
+                ("Chain", Newtype(array(Ref("CommandSpec")))),
+                ("OneOf", Newtype(array(Ref("CommandSpec")))),
+                ("Opt", Newtype(Ref("CommandSpec"))),
+                (
+                    "Many",
+                    fields(vec![
+                        field("spec", Ref("CommandSpec")),
+                        optional("min", opt(Unsigned)),
+                        optional("max", opt(Unsigned)),
+                        field("delim", String),
+                    ]),
+                ),
+                (
+                    "Doc",
+                    fields(vec![
+                        field("name", String),
+                        optional("desc", opt(String)),
+                        field("spec", Ref("CommandSpec")),
+                    ]),
+                ),
+            ]),
+        },
+        def("Patch", Kind::Alias(array(Ref("PatchOperation")))),
+        Definition {
+            description: Some("A JSON Patch (RFC 6902) operation."),
+            ..def_struct(
+                "PatchOperation",
+                vec![
+                    field("op", String),
+                    field("path", String),
+                    optional("from", String),
+                    optional("value", Any),
+                ],
+            )
+        },
+        Definition {
+            description: Some("A request for `bot_cli::cli`."),
+            ..def_struct(
+                "BotRequest",
+                vec![
+                    field("player", Unsigned),
+                    field("player_state", String),
+                    field("players", strings()),
+                    field("command_spec", Ref("CommandSpec")),
+                    optional("game_id", opt(String)),
+                ],
+            )
+        },
+        Definition {
+            description: Some("The commands a bot wants to play, from `bot_cli::cli`."),
+            ..def("BotResponse", Kind::Alias(strings()))
+        },
+    ]
+}
+
+/// Checks a serialised value matches the named type from `definitions`, returning the path of the
+/// first mismatch.
+pub fn validate(value: &Value, name: &str) -> Result<(), String> {
+    validate_definition(&definitions(), value, name, "")
+}
+
+fn validate_definition(
+    definitions: &[Definition],
+    value: &Value,
+    name: &str,
+    path: &str,
+) -> Result<(), String> {
+    let d = definitions
+        .iter()
+        .find(|d| d.name == name)
+        .ok_or_else(|| format!("{}: unknown type {}", path, name))?;
+    match d.kind {
+        Kind::Struct(ref f) => validate_fields(definitions, value, f, path),
+        Kind::Enum(ref variants) => {
+            let (variant, fields_value) = match *value {
+                Value::String(ref s) => (s.as_str(), None),
+                Value::Object(ref o) if o.len() == 1 => {
+                    let (k, v) = o.iter().next().unwrap();
+                    (k.as_str(), Some(v))
+                }
+                _ => return Err(format!("{}: expected a {} variant", path, name)),
+            };
+            let variant_path = format!("{}/{}", path, variant);
+            match (variants.iter().find(|v| v.0 == variant), fields_value) {
+                (Some(&(_, Variant::Unit)), None) => Ok(()),
+                (Some(&(_, Variant::Newtype(ref shape))), Some(v)) => {
+                    validate_shape(definitions, v, shape, &variant_path)
+                }
+                (Some(&(_, Variant::Struct(ref f))), Some(v)) => {
+                    validate_fields(definitions, v, f, &variant_path)
+                }
+                _ => Err(format!("{}: unexpected {} variant {}", path, name, variant)),
+            }
+        }
+        Kind::Alias(ref shape) => validate_shape(definitions, value, shape, path),
+    }
+}
+
+fn validate_fields(
+    definitions: &[Definition],
+    value: &Value,
+    f: &Fields,
+    path: &str,
+) -> Result<(), String> {
+    let o = value
+        .as_object()
+        .ok_or_else(|| format!("{}: expected an object", path))?;
+    for field in &f.fields {
+        let path = format!("{}/{}", path, field.name);
+        match o.get(field.name) {
+            Some(v) => validate_shape(definitions, v, &field.shape, &path)?,
+            None if field.optional => {}
+            None => return Err(format!("{}: missing", path)),
+        }
+    }
+    let undefined = o.keys().find(|k| !f.fields.iter().any(|field| field.name == *k));
+    match undefined {
+        Some(k) => Err(format!("{}/{}: not defined", path, k)),
+        None => Ok(()),
+    }
+}
+
+fn validate_shape(
+    definitions: &[Definition],
+    value: &Value,
+    shape: &Shape,
+    path: &str,
+) -> Result<(), String> {
+    let valid = match *shape {
+        Shape::Bool => value.is_boolean(),
+        Shape::Integer => value.is_i64() || value.is_u64(),
+        Shape::Unsigned => value.is_u64(),
+        Shape::Number => value.is_number(),
+        Shape::String => value.is_string(),
+        Shape::Any => true,
+        Shape::Option(_) if value.is_null() => true,
+        Shape::Option(ref inner) => return validate_shape(definitions, value, inner, path),
+        Shape::Array(ref inner) => match value.as_array() {
+            Some(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    validate_shape(definitions, item, inner, &format!("{}/{}", path, i))?;
+                }
+                true
+            }
+            None => false,
+        },
+        Shape::Map(ref inner) => match value.as_object() {
+            Some(o) => {
+                for (k, v) in o {
+                    validate_shape(definitions, v, inner, &format!("{}/{}", path, k))?;
+                }
+                true
+            }
+            None => false,
+        },
+        Shape::Ref(name) => return validate_definition(definitions, value, name, path),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("{}: expected {:?}", path, shape))
+    }
+}
+
+/// Creates a JSON Schema (draft 7) document containing every type from `definitions` under
+/// `definitions`.
+pub fn json_schema() -> Value {
+    let definitions = definitions()
+        .iter()
+        .map(|d| {
+            let mut schema = kind_schema(&d.kind);
+            if let (Some(description), Some(o)) = (d.description, schema.as_object_mut()) {
+                o.insert("description".to_string(), description.into());
+            }
+            (d.name.to_string(), schema)
+        })
+        .collect::<Map<String, Value>>();
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "brdgme-cmd protocol",
+        "definitions": definitions,
+    })
+}
+
+fn kind_schema(kind: &Kind) -> Value {
+    match *kind {
+        Kind::Struct(ref f) => fields_schema(f),
+        Kind::Enum(ref variants) => json!({
+            "oneOf": variants
+                .iter()
+                .map(|&(name, ref variant)| match *variant {
+                    Variant::Unit => json!({ "const": name }),
+                    Variant::Newtype(ref shape) => tagged_schema(name, shape_schema(shape)),
+                    Variant::Struct(ref f) => tagged_schema(name, fields_schema(f)),
+                })
+                .collect::<Vec<Value>>(),
+        }),
+        Kind::Alias(ref shape) => shape_schema(shape),
+    }
+}
+
+/// An object with `name` as the only key.
+fn tagged_schema(name: &str, schema: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": { name: schema },
+        "required": [name],
+        "additionalProperties": false,
+    })
+}
+
+fn fields_schema(f: &Fields) -> Value {
+    json!({
+        "type": "object",
+        "properties": f
+            .fields
+            .iter()
+            .map(|field| (field.name.to_string(), shape_schema(&field.shape)))
+            .collect::<Map<String, Value>>(),
+        "required": f
+            .fields
+            .iter()
+            .filter(|field| !field.optional)
+            .map(|field| field.name)
+            .collect::<Vec<&str>>(),
+        "additionalProperties": false,
+    })
+}
+
+fn shape_schema(shape: &Shape) -> Value {
+    match *shape {
+        Shape::Bool => json!({ "type": "boolean" }),
+        Shape::Integer => json!({ "type": "integer" }),
+        Shape::Unsigned => json!({ "type": "integer", "minimum": 0 }),
+        Shape::Number => json!({ "type": "number" }),
+        Shape::String => json!({ "type": "string" }),
+        Shape::Any => json!({}),
+        Shape::Option(ref inner) => json!({
+            "anyOf": [shape_schema(inner), { "type": "null" }],
+        }),
+        Shape::Array(ref inner) => json!({
+            "type": "array",
+            "items": shape_schema(inner),
+        }),
+        Shape::Map(ref inner) => json!({
+            "type": "object",
+            "additionalProperties": shape_schema(inner),
+        }),
+        Shape::Ref(name) => json!({ "$ref": format!("#/definitions/{}", name) }),
+    }
+}
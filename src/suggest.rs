@@ -0,0 +1,326 @@
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+
+use brdgme_game::command::Spec as CommandSpec;
+
+/// Integer ranges up to this size have each value suggested.
+const MAX_INT_SUGGESTIONS: i64 = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Suggestion {
+    /// The next token which could be typed.
+    pub token: String,
+    /// The partial input completed with `token`.
+    pub command: String,
+}
+
+/// Finds tokens which could follow a partial command. The second value is true if the input is
+/// already a complete command.
+pub fn suggest(spec: &CommandSpec, input: &str, names: &[String]) -> (Vec<Suggestion>, bool) {
+    let mut walker = Walker {
+        input,
+        names,
+        suggestions: BTreeSet::new(),
+    };
+    let ends = walker.walk(spec, 0);
+    (
+        walker.suggestions.into_iter().collect(),
+        ends.contains(&input.len()),
+    )
+}
+
+struct Walker<'a> {
+    input: &'a str,
+    names: &'a [String],
+    suggestions: BTreeSet<Suggestion>,
+}
+
+impl<'a> Walker<'a> {
+    /// Returns every offset the spec could finish parsing at when starting at `pos`, recording a
+    /// suggestion whenever the input runs out partway through a token.
+    fn walk(&mut self, spec: &CommandSpec, pos: usize) -> BTreeSet<usize> {
+        match *spec {
+            CommandSpec::Token(ref token) => self.tokens(pos, &[token.to_owned()], true),
+            CommandSpec::Enum {
+                ref values,
+                exact,
+            } => self.tokens(pos, values, exact),
+            CommandSpec::Player => {
+                let names = self.names;
+                self.tokens(pos, names, false)
+            }
+            CommandSpec::Int { min, max } => self.int(pos, min, max),
+            CommandSpec::Space => {
+                let rest = &self.input[pos..];
+                let trimmed = rest.trim_start();
+                let mut ends = BTreeSet::new();
+                if rest.is_empty() || trimmed.len() < rest.len() {
+                    // Reaching the end of input here still lets following tokens be suggested.
+                    ends.insert(pos + rest.len() - trimmed.len());
+                }
+                ends
+            }
+            CommandSpec::Chain(ref specs) => {
+                let mut ends: BTreeSet<usize> = Some(pos).into_iter().collect();
+                for s in specs {
+                    ends = ends.iter().flat_map(|&p| self.walk(s, p)).collect();
+                }
+                ends
+            }
+            CommandSpec::OneOf(ref specs) => specs.iter().flat_map(|s| self.walk(s, pos)).collect(),
+            CommandSpec::Opt(ref spec) => {
+                let mut ends = self.walk(spec, pos);
+                ends.insert(pos);
+                ends
+            }
+            CommandSpec::Many {
+                ref spec,
+                min,
+                max,
+                ref delim,
+            } => {
+                let mut ends = BTreeSet::new();
+                let mut current: BTreeSet<usize> = Some(pos).into_iter().collect();
+                let mut count = 0;
+                loop {
+                    if min.map_or(true, |min| count >= min) {
+                        ends.extend(current.iter().cloned());
+                    }
+                    if max.map_or(false, |max| count >= max) || current.is_empty() {
+                        break;
+                    }
+                    let starts: BTreeSet<usize> = if count == 0 {
+                        current
+                    } else {
+                        current.iter().flat_map(|&p| self.delim(p, delim)).collect()
+                    };
+                    let next: BTreeSet<usize> = starts
+                        .iter()
+                        .flat_map(|&p| self.walk(spec, p))
+                        .filter(|&p| !ends.contains(&p))
+                        .collect();
+                    current = next;
+                    count += 1;
+                }
+                ends
+            }
+            CommandSpec::Doc { ref spec, .. } => self.walk(spec, pos),
+        }
+    }
+
+    fn skip_space(&self, pos: usize) -> usize {
+        let rest = &self.input[pos..];
+        pos + rest.len() - rest.trim_start().len()
+    }
+
+    /// Matches the delimiter between repetitions of `Many`, which may be surrounded by whitespace.
+    /// The delimiter is suggested directly after the previous repetition without a space.
+    fn delim(&mut self, pos: usize, delim: &str) -> BTreeSet<usize> {
+        let delim = delim.trim();
+        let pos = self.skip_space(pos);
+        let input = self.input;
+        let rest = &input[pos..];
+        let mut ends = BTreeSet::new();
+        if delim.is_empty() {
+            ends.insert(pos);
+        } else if starts_with_ignore_case(rest, delim) {
+            ends.insert(self.skip_space(pos + delim.len()));
+        } else if starts_with_ignore_case(delim, rest) {
+            self.suggestions.insert(Suggestion {
+                token: delim.to_owned(),
+                command: format!("{}{}", &input[..pos], delim),
+            });
+        }
+        ends
+    }
+
+    fn tokens(&mut self, pos: usize, tokens: &[String], exact: bool) -> BTreeSet<usize> {
+        let rest = &self.input[pos..];
+        let word_len = rest.find(char::is_whitespace).unwrap_or_else(|| rest.len());
+        let mut ends = BTreeSet::new();
+        for token in tokens {
+            if starts_with_ignore_case(rest, token) {
+                ends.insert(pos + token.len());
+            } else if word_len == rest.len() && starts_with_ignore_case(token, rest) {
+                self.suggest(pos, token);
+            } else if !exact && word_len > 0 && starts_with_ignore_case(token, &rest[..word_len])
+            {
+                ends.insert(pos + word_len);
+            }
+        }
+        ends
+    }
+
+    fn int(&mut self, pos: usize, min: Option<i32>, max: Option<i32>) -> BTreeSet<usize> {
+        let rest = &self.input[pos..];
+        let sign_len = if rest.starts_with('-') { 1 } else { 0 };
+        let digits_len = rest[sign_len..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or_else(|| rest.len() - sign_len);
+        let mut ends = BTreeSet::new();
+        if digits_len > 0 {
+            ends.insert(pos + sign_len + digits_len);
+        }
+        if sign_len + digits_len == rest.len() {
+            if let (Some(min), Some(max)) = (min, max) {
+                // Widened so the full i32 range can't overflow.
+                if max >= min && i64::from(max) - i64::from(min) < MAX_INT_SUGGESTIONS {
+                    for n in min..=max {
+                        let n = n.to_string();
+                        if n.starts_with(rest) && n != rest {
+                            self.suggest(pos, &n);
+                        }
+                    }
+                }
+            }
+        }
+        ends
+    }
+
+    fn suggest(&mut self, pos: usize, token: &str) {
+        let before = &self.input[..pos];
+        // A trailing `Space` matches the end of input, so the space may still need adding.
+        let sep = if pos == self.input.len() && !before.is_empty()
+            && !before.ends_with(char::is_whitespace)
+        {
+            " "
+        } else {
+            ""
+        };
+        self.suggestions.insert(Suggestion {
+            token: token.to_owned(),
+            command: format!("{}{}{}", before, sep, token),
+        });
+    }
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len() && s.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enum_spec(values: &[&str]) -> CommandSpec {
+        CommandSpec::Enum {
+            values: values.iter().map(|v| v.to_string()).collect(),
+            exact: true,
+        }
+    }
+
+    fn many(spec: CommandSpec, delim: &str) -> CommandSpec {
+        CommandSpec::Many {
+            spec: Box::new(spec),
+            min: Some(1),
+            max: Some(2),
+            delim: delim.to_string(),
+        }
+    }
+
+    fn suggestions(pairs: &[(&str, &str)]) -> Vec<Suggestion> {
+        pairs
+            .iter()
+            .map(|&(token, command)| Suggestion {
+                token: token.to_string(),
+                command: command.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn token() {
+        let spec = CommandSpec::Token("play".to_string());
+        assert_eq!(
+            suggest(&spec, "pl", &[]),
+            (suggestions(&[("play", "play")]), false)
+        );
+        assert_eq!(suggest(&spec, "play", &[]), (vec![], true));
+    }
+
+    #[test]
+    fn enum_values() {
+        let spec = enum_spec(&["red", "green", "grey"]);
+        assert_eq!(
+            suggest(&spec, "gr", &[]),
+            (suggestions(&[("green", "green"), ("grey", "grey")]), false)
+        );
+        assert_eq!(suggest(&spec, "grey", &[]), (vec![], true));
+    }
+
+    #[test]
+    fn int() {
+        let spec = CommandSpec::Int {
+            min: Some(1),
+            max: Some(12),
+        };
+        assert_eq!(
+            suggest(&spec, "1", &[]),
+            (suggestions(&[("10", "10"), ("11", "11"), ("12", "12")]), true)
+        );
+    }
+
+    #[test]
+    fn int_full_range() {
+        let spec = CommandSpec::Int {
+            min: Some(i32::MIN),
+            max: Some(i32::MAX),
+        };
+        assert_eq!(suggest(&spec, "1", &[]), (vec![], true));
+    }
+
+    #[test]
+    fn player() {
+        let names = vec!["alice".to_string(), "bob".to_string()];
+        let spec = CommandSpec::Chain(vec![
+            CommandSpec::Token("give".to_string()),
+            CommandSpec::Space,
+            CommandSpec::Player,
+        ]);
+        let expected = suggestions(&[("alice", "give alice"), ("bob", "give bob")]);
+        assert_eq!(suggest(&spec, "give", &names), (expected.clone(), false));
+        assert_eq!(suggest(&spec, "give ", &names), (expected, false));
+        assert_eq!(suggest(&spec, "give bob", &names), (vec![], true));
+    }
+
+    #[test]
+    fn opt() {
+        let spec = CommandSpec::Chain(vec![
+            CommandSpec::Token("pass".to_string()),
+            CommandSpec::Opt(Box::new(CommandSpec::Chain(vec![
+                CommandSpec::Space,
+                CommandSpec::Token("now".to_string()),
+            ]))),
+        ]);
+        assert_eq!(
+            suggest(&spec, "pass", &[]),
+            (suggestions(&[("now", "pass now")]), true)
+        );
+    }
+
+    #[test]
+    fn many_space_delim() {
+        let spec = many(enum_spec(&["red", "blue"]), " ");
+        assert_eq!(
+            suggest(&spec, "red b", &[]),
+            (suggestions(&[("blue", "red blue")]), false)
+        );
+        assert_eq!(suggest(&spec, "red blue", &[]), (vec![], true));
+    }
+
+    #[test]
+    fn many_custom_delim() {
+        let spec = many(enum_spec(&["red", "blue"]), ",");
+        assert_eq!(
+            suggest(&spec, "red", &[]),
+            (suggestions(&[(",", "red,")]), true)
+        );
+        assert_eq!(
+            suggest(&spec, "red, bl", &[]),
+            (suggestions(&[("blue", "red, blue")]), false)
+        );
+        assert_eq!(suggest(&spec, "red,blue", &[]), (vec![], true));
+        assert_eq!(suggest(&spec, "red blue", &[]), (vec![], false));
+    }
+}
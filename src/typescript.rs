@@ -0,0 +1,88 @@
+use crate::schema::{self, Definition, Fields, Kind, Shape, Variant};
+
+/// Creates TypeScript definitions for every type from `schema::definitions`, matching their serde
+/// representation. `tests/typescript.rs` checks the definitions still match the serde types before
+/// comparing against the snapshot.
+pub fn typescript() -> String {
+    let mut out = "// Generated by brdgme_cmd::typescript, do not edit.\n".to_string();
+    for d in schema::definitions() {
+        out.push('\n');
+        out.push_str(&definition(&d));
+    }
+    out
+}
+
+fn definition(d: &Definition) -> String {
+    let mut out = String::new();
+    if let Some(description) = d.description {
+        out.push_str(&format!("/** {} */\n", description));
+    }
+    match d.kind {
+        Kind::Struct(ref f) => {
+            out.push_str(&format!("export interface {} {{\n", d.name));
+            for field in field_types(f) {
+                out.push_str(&format!("  {};\n", field));
+            }
+            out.push_str("}\n");
+        }
+        Kind::Enum(ref variants) => {
+            out.push_str(&format!("export type {} =\n", d.name));
+            for &(name, ref variant) in variants {
+                out.push_str(&match *variant {
+                    Variant::Unit => format!("  | \"{}\"", name),
+                    Variant::Newtype(ref shape) => {
+                        format!("  | {{ {}: {} }}", name, shape_type(shape))
+                    }
+                    Variant::Struct(ref f) => format!("  | {{ {}: {} }}", name, inline_object(f)),
+                });
+                out.push('\n');
+            }
+            // Terminates the final variant.
+            out.pop();
+            out.push_str(";\n");
+        }
+        Kind::Alias(ref shape) => {
+            out.push_str(&format!("export type {} = {};\n", d.name, shape_type(shape)));
+        }
+    }
+    out
+}
+
+fn field_types(f: &Fields) -> Vec<String> {
+    f.fields
+        .iter()
+        .map(|field| {
+            format!(
+                "{}{}: {}",
+                field.name,
+                if field.optional { "?" } else { "" },
+                shape_type(&field.shape)
+            )
+        })
+        .collect()
+}
+
+fn inline_object(f: &Fields) -> String {
+    let fields = field_types(f);
+    if fields.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{ {} }}", fields.join("; "))
+    }
+}
+
+fn shape_type(shape: &Shape) -> String {
+    match *shape {
+        Shape::Bool => "boolean".to_string(),
+        Shape::Integer | Shape::Unsigned | Shape::Number => "number".to_string(),
+        Shape::String => "string".to_string(),
+        Shape::Any => "unknown".to_string(),
+        Shape::Option(ref inner) => format!("{} | null", shape_type(inner)),
+        Shape::Array(ref inner) => match **inner {
+            Shape::Option(_) => format!("({})[]", shape_type(inner)),
+            _ => format!("{}[]", shape_type(inner)),
+        },
+        Shape::Map(ref inner) => format!("{{ [key: string]: {} }}", shape_type(inner)),
+        Shape::Ref(name) => name.to_string(),
+    }
+}
@@ -0,0 +1,96 @@
+use serde_json::{json, Value};
+
+use brdgme_game::Status;
+
+use brdgme_cmd::api::{GameResponse, PlayerRender, PubRender, Snapshot, StateFormat, StateKey};
+use brdgme_cmd::encoding::Compression;
+
+fn snapshot(format: &StateFormat, turn: usize) -> Snapshot {
+    Snapshot {
+        game: GameResponse {
+            state: format
+                .encode(&json!({"board": [1, 2, turn], "turn": turn}))
+                .unwrap(),
+            points: vec![turn as f32, 0.0],
+            status: Status::Active {
+                whose_turn: vec![turn % 2],
+                eliminated: vec![],
+            },
+            options: None,
+        },
+        public_render: PubRender {
+            pub_state: json!({ "turn": turn }).to_string(),
+            render: format!("turn {}", turn),
+        },
+        player_renders: vec![PlayerRender {
+            player_state: json!({ "turn": turn, "hand": [turn] }).to_string(),
+            render: format!("your turn {}", turn),
+            command_spec: None,
+        }],
+    }
+}
+
+fn parsed(json: &str) -> Value {
+    serde_json::from_str(json).unwrap()
+}
+
+/// Applies the diff between `from` and `to` to `from`, checking the result matches `to`. Game
+/// states are compared by their decoded envelopes as reencoding may reorder keys.
+fn assert_round_trip(format: &StateFormat, from: &Snapshot, to: &Snapshot) -> Snapshot {
+    let applied = from.apply(&from.diff(to).unwrap()).unwrap();
+    assert_eq!(
+        format.decode(&applied.game.state).unwrap(),
+        format.decode(&to.game.state).unwrap()
+    );
+    assert_eq!(applied.game.points, to.game.points);
+    assert_eq!(
+        serde_json::to_value(&applied.game.status).unwrap(),
+        serde_json::to_value(&to.game.status).unwrap()
+    );
+    assert_eq!(
+        parsed(&applied.public_render.pub_state),
+        parsed(&to.public_render.pub_state)
+    );
+    assert_eq!(applied.public_render.render, to.public_render.render);
+    assert_eq!(applied.player_renders.len(), to.player_renders.len());
+    for (a, t) in applied.player_renders.iter().zip(&to.player_renders) {
+        assert_eq!(parsed(&a.player_state), parsed(&t.player_state));
+        assert_eq!(a.render, t.render);
+    }
+    applied
+}
+
+#[test]
+fn plain_round_trip() {
+    let format = StateFormat {
+        state_version: 1,
+        key: None,
+    };
+    assert_round_trip(&format, &snapshot(&format, 0), &snapshot(&format, 1));
+}
+
+#[test]
+fn signed_round_trip() {
+    let format = StateFormat {
+        state_version: 1,
+        key: Some(StateKey::new("secret").unwrap()),
+    };
+    let to = snapshot(&format, 1);
+    let applied = assert_round_trip(&format, &snapshot(&format, 0), &to);
+    // The signature covers the exact inner string, so it must survive the patch untouched.
+    assert_eq!(
+        parsed(&applied.game.state)["state"],
+        parsed(&to.game.state)["state"]
+    );
+}
+
+#[test]
+fn compressed_round_trip() {
+    let format = StateFormat::default();
+    for &compression in &[Compression::Gzip, Compression::Zstd] {
+        let mut from = snapshot(&format, 0);
+        // Clients hold the compressed state they were sent.
+        from.game.state = compression.compress(&from.game.state).unwrap();
+        assert_round_trip(&format, &from, &snapshot(&format, 1));
+    }
+}
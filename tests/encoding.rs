@@ -0,0 +1,145 @@
+use failure::Error;
+
+use std::io::{BufRead, Cursor, Read};
+
+use brdgme_game::Status;
+
+use brdgme_cmd::api::{GameResponse, PubRender, Request, Response};
+use brdgme_cmd::cli;
+use brdgme_cmd::encoding::{self, Codec, Compression, Encoding};
+use brdgme_cmd::requester::Requester;
+
+const STATE: &str = r#"{"state_version":1,"game":{"board":[1,2,3]}}"#;
+
+/// Echoes the game from `Request::Status`, other requests get `Response::PlayerCounts`.
+struct Echo;
+
+impl Requester for Echo {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        Ok(match *req {
+            Request::Status { ref game } => Response::Status {
+                game: GameResponse {
+                    state: game.to_owned(),
+                    points: vec![],
+                    status: Status::Active {
+                        whose_turn: vec![0],
+                        eliminated: vec![],
+                    },
+                    options: None,
+                },
+                public_render: PubRender {
+                    pub_state: "{}".to_string(),
+                    render: String::new(),
+                },
+                player_renders: vec![],
+            },
+            _ => Response::PlayerCounts {
+                player_counts: vec![2],
+            },
+        })
+    }
+}
+
+fn codecs() -> Vec<Codec> {
+    let compressions = [None, Some(Compression::Gzip), Some(Compression::Zstd)];
+    [Encoding::Json, Encoding::MessagePack, Encoding::Cbor]
+        .iter()
+        .flat_map(|&encoding| {
+            compressions.iter().map(move |&compression| Codec {
+                encoding,
+                compression,
+            })
+        }).collect()
+}
+
+/// A status request for `STATE`, compressed the way a client would send it back.
+fn status_request(codec: Codec) -> Request {
+    Request::Status {
+        game: match codec.compression {
+            Some(compression) => compression.compress(STATE).unwrap(),
+            None => STATE.to_string(),
+        },
+    }
+}
+
+fn assert_state(codec: Codec, resp: &Response) {
+    let state = resp.game_state().expect("expected a game state");
+    assert_eq!(codec.compression.is_some(), state != STATE, "{:?}", codec);
+    assert_eq!(encoding::decompress_state(state).unwrap(), STATE);
+}
+
+#[test]
+fn codec_round_trip() {
+    for codec in codecs() {
+        let req = codec
+            .decode_request(&codec.encoding.encode(&status_request(codec)).unwrap())
+            .unwrap();
+        match req {
+            Request::Status { ref game } => assert_eq!(game, STATE, "{:?}", codec),
+            ref req => panic!("unexpected request {:?}", req),
+        }
+        let resp = codec.encode_response(Echo.request(&req).unwrap()).unwrap();
+        assert_state(codec, &codec.encoding.decode(&resp).unwrap());
+    }
+}
+
+#[test]
+fn decompress_uncompressed_state() {
+    assert_eq!(encoding::decompress_state(STATE).unwrap(), STATE);
+}
+
+#[test]
+fn decompress_invalid_state() {
+    assert!(encoding::decompress_state("gzip:not base64!").is_err());
+}
+
+#[test]
+fn one_shot_framing() {
+    for codec in codecs() {
+        let input = codec.encoding.encode(&status_request(codec)).unwrap();
+        let mut output = vec![];
+        cli::cli_encoded(&mut Echo, input.as_slice(), &mut output, codec);
+        assert_state(codec, &codec.encoding.decode(&output).unwrap());
+    }
+}
+
+#[test]
+fn persistent_framing() {
+    for codec in codecs() {
+        let mut input = vec![];
+        for req in &[Request::PlayerCounts, status_request(codec)] {
+            let message = codec.encoding.encode(req).unwrap();
+            codec.encoding.write_message(&mut input, &message).unwrap();
+        }
+        let mut output = vec![];
+        cli::serve_encoded(&mut Echo, Cursor::new(input), &mut output, codec);
+
+        let mut output = Cursor::new(output);
+        let mut responses: Vec<Response> = vec![];
+        loop {
+            let message = if codec.encoding.is_binary() {
+                match encoding::read_message_len(&mut output).unwrap() {
+                    Some(len) => {
+                        let mut message = vec![0; len];
+                        output.read_exact(&mut message).unwrap();
+                        message
+                    }
+                    None => break,
+                }
+            } else {
+                let mut line = vec![];
+                if output.read_until(b'\n', &mut line).unwrap() == 0 {
+                    break;
+                }
+                line
+            };
+            responses.push(codec.encoding.decode(&message).unwrap());
+        }
+        assert_eq!(responses.len(), 2, "{:?}", codec);
+        match responses[0] {
+            Response::PlayerCounts { ref player_counts } => assert_eq!(player_counts, &[2]),
+            ref resp => panic!("unexpected response {:?}", resp),
+        }
+        assert_state(codec, &responses[1]);
+    }
+}
@@ -0,0 +1,252 @@
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use brdgme_game::command::Spec as CommandSpec;
+use brdgme_game::errors::GameError;
+use brdgme_game::{CommandResponse, Gamer, Log, Renderer, Status};
+use brdgme_markup::Node;
+
+use brdgme_cmd::api::{Request, Response, StateFormat};
+use brdgme_cmd::requester::gamer;
+use brdgme_cmd::requester::Requester;
+
+/// Players take turns incrementing a shared count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Counter {
+    players: usize,
+    count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct View {
+    count: usize,
+}
+
+impl Renderer for View {
+    fn render(&self) -> Vec<Node> {
+        vec![Node::text(format!("count {}", self.count))]
+    }
+}
+
+impl Gamer for Counter {
+    type PubState = View;
+    type PlayerState = View;
+
+    fn new(players: usize) -> Result<(Self, Vec<Log>), GameError> {
+        Ok((Counter { players, count: 0 }, vec![]))
+    }
+
+    fn pub_state(&self) -> View {
+        View { count: self.count }
+    }
+
+    fn player_state(&self, _player: usize) -> View {
+        View { count: self.count }
+    }
+
+    fn command(
+        &mut self,
+        player: usize,
+        _input: &str,
+        _players: &[String],
+    ) -> Result<CommandResponse, GameError> {
+        if player != self.count % self.players {
+            return Err(GameError::NotYourTurn);
+        }
+        self.count += 1;
+        Ok(CommandResponse {
+            logs: vec![],
+            can_undo: false,
+            remaining_input: String::new(),
+        })
+    }
+
+    fn status(&self) -> Status {
+        Status::Active {
+            whose_turn: vec![self.count % self.players],
+            eliminated: vec![],
+        }
+    }
+
+    fn command_spec(&self, _player: usize) -> Option<CommandSpec> {
+        None
+    }
+
+    fn player_counts() -> Vec<usize> {
+        vec![2]
+    }
+
+    fn player_count(&self) -> usize {
+        self.players
+    }
+
+    fn points(&self) -> Vec<f32> {
+        vec![0.0; self.players]
+    }
+}
+
+fn request<R: Requester>(requester: &mut R, req: Request) -> Response {
+    requester.request(&req).unwrap()
+}
+
+fn play(player: usize, game: &str, diff: bool) -> Request {
+    Request::Play {
+        player,
+        command: "inc".to_string(),
+        names: vec![],
+        game: game.to_string(),
+        diff,
+    }
+}
+
+/// The count in a game state encoded with `format`.
+fn count(format: &StateFormat, state: &str) -> Value {
+    format.decode(state).unwrap().game["count"].clone()
+}
+
+#[test]
+fn batch_chains_through_play_diff() {
+    let mut requester = gamer::new::<Counter>();
+    let new = request(
+        &mut requester,
+        Request::New {
+            players: 2,
+            seed: None,
+            options: None,
+        },
+    );
+    let state = new.game_state().unwrap().to_owned();
+    let responses = match request(
+        &mut requester,
+        Request::Batch {
+            requests: vec![play(0, &state, true), play(1, &state, false)],
+            chain: true,
+        },
+    ) {
+        Response::Batch { responses } => responses,
+        resp => panic!("expected a batch response, got {:?}", resp),
+    };
+    assert_eq!(responses.len(), 2);
+    match responses[0] {
+        Response::PlayDiff { .. } => {}
+        ref resp => panic!("expected a play diff response, got {:?}", resp),
+    }
+    // The second play only succeeds as player 1 if it continued from the first.
+    let state = responses[1]
+        .game_state()
+        .unwrap_or_else(|| panic!("expected a play response, got {:?}", responses[1]));
+    assert_eq!(count(&StateFormat::default(), state), json!(2));
+}
+
+/// Version 1 states called the count `total`.
+fn migrate(mut game: Value, from_version: u32) -> Result<Value, String> {
+    if from_version != 1 {
+        return Err(format!("no migration from version {}", from_version));
+    }
+    let total = game["total"].take();
+    game["count"] = total;
+    Ok(game)
+}
+
+fn versioned_state(state_version: u32, game: Value) -> String {
+    StateFormat {
+        state_version,
+        key: None,
+    }
+    .encode(&game)
+    .unwrap()
+}
+
+fn assert_state_version_error(resp: Response, state_version: u32, current_version: u32) {
+    match resp {
+        Response::StateVersionError {
+            state_version: s,
+            current_version: c,
+            ..
+        } => assert_eq!((s, c), (state_version, current_version)),
+        resp => panic!("expected a state version error, got {:?}", resp),
+    }
+}
+
+#[test]
+fn legacy_state_is_version_zero() {
+    let resp = request(
+        &mut gamer::new::<Counter>(),
+        Request::Status {
+            game: json!({"players": 2, "count": 1}).to_string(),
+        },
+    );
+    assert_eq!(
+        count(&StateFormat::default(), resp.game_state().unwrap()),
+        json!(1)
+    );
+}
+
+#[test]
+fn older_version_needs_migrating() {
+    let old = versioned_state(1, json!({"players": 2, "total": 3}));
+    let resp = request(
+        &mut gamer::new::<Counter>().versioned(2, migrate),
+        Request::Status { game: old },
+    );
+    assert_state_version_error(resp, 1, 2);
+}
+
+#[test]
+fn older_version_is_migrated() {
+    let old = versioned_state(1, json!({"players": 2, "total": 3}));
+    let resp = request(
+        &mut gamer::new::<Counter>().versioned(2, migrate),
+        Request::Migrate {
+            game: old,
+            from_version: None,
+        },
+    );
+    let state = match resp {
+        Response::Migrate { game, .. } => game.state,
+        resp => panic!("expected a migrate response, got {:?}", resp),
+    };
+    let envelope = StateFormat::default().decode(&state).unwrap();
+    assert_eq!(envelope.state_version, 2);
+    assert_eq!(envelope.game["count"], json!(3));
+}
+
+#[test]
+fn failed_migration_is_rejected() {
+    let old = versioned_state(0, json!({"players": 2, "count": 3}));
+    let resp = request(
+        &mut gamer::new::<Counter>().versioned(2, migrate),
+        Request::Migrate {
+            game: old,
+            from_version: None,
+        },
+    );
+    assert_state_version_error(resp, 0, 2);
+}
+
+#[test]
+fn newer_version_is_rejected() {
+    let new = versioned_state(3, json!({"players": 2, "count": 3}));
+    let resp = request(
+        &mut gamer::new::<Counter>().versioned(2, migrate),
+        Request::Migrate {
+            game: new,
+            from_version: None,
+        },
+    );
+    assert_state_version_error(resp, 3, 2);
+}
+
+#[test]
+fn migrate_without_hook_is_rejected() {
+    let state = versioned_state(1, json!({"players": 2, "count": 3}));
+    let resp = request(
+        &mut gamer::new::<Counter>(),
+        Request::Migrate {
+            game: state,
+            from_version: None,
+        },
+    );
+    assert_state_version_error(resp, 1, 0);
+}
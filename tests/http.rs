@@ -0,0 +1,106 @@
+use failure::Error;
+use serde_json::{json, Value};
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use brdgme_cmd::api::{Request, Response};
+use brdgme_cmd::http;
+use brdgme_cmd::requester::Requester;
+
+/// Responds to every request with the name of the request.
+struct Echo;
+
+impl Requester for Echo {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        Ok(Response::SystemError {
+            message: req.name().to_string(),
+        })
+    }
+}
+
+fn server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || http::serve_listener(&mut Echo, listener));
+    addr
+}
+
+/// Sends a request and returns the status line and body.
+fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> (String, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    ).unwrap();
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).unwrap();
+    let mut parts = resp.splitn(2, "\r\n\r\n");
+    let status = parts.next().unwrap().lines().next().unwrap().to_string();
+    (status, serde_json::from_str(parts.next().unwrap()).unwrap())
+}
+
+fn echoed(name: &str) -> Value {
+    json!({ "SystemError": { "message": name } })
+}
+
+#[test]
+fn request_path() {
+    let addr = server();
+    assert_eq!(
+        send(addr, "POST", "/request", r#""PlayerCounts""#),
+        ("HTTP/1.1 200 OK".to_string(), echoed("PlayerCounts"))
+    );
+}
+
+#[test]
+fn variant_path() {
+    let addr = server();
+    let body = r#"{"player": 0, "command": "pass", "names": [], "game": "{}"}"#;
+    assert_eq!(
+        send(addr, "POST", "/play", body),
+        ("HTTP/1.1 200 OK".to_string(), echoed("Play"))
+    );
+}
+
+#[test]
+fn unknown_path() {
+    let addr = server();
+    assert_eq!(
+        send(addr, "POST", "/nope", "").0,
+        "HTTP/1.1 404 Not Found".to_string()
+    );
+}
+
+#[test]
+fn wrong_method() {
+    let addr = server();
+    assert_eq!(
+        send(addr, "GET", "/request", "").0,
+        "HTTP/1.1 405 Method Not Allowed".to_string()
+    );
+}
+
+#[test]
+fn idle_connection_does_not_block() {
+    let addr = server();
+    let _idle = TcpStream::connect(addr).unwrap();
+    assert_eq!(
+        send(addr, "POST", "/player_counts", "").0,
+        "HTTP/1.1 200 OK".to_string()
+    );
+}
+
+#[test]
+fn unit_variant_with_empty_body() {
+    let addr = server();
+    assert_eq!(
+        send(addr, "POST", "/hello", "{}"),
+        ("HTTP/1.1 200 OK".to_string(), echoed("Hello"))
+    );
+}
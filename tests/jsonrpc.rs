@@ -0,0 +1,142 @@
+use failure::{format_err, Error};
+use serde_json::{json, Value};
+
+use brdgme_cmd::api::{ErrorKind, Request, Response, Span};
+use brdgme_cmd::jsonrpc::{self, RpcResponse};
+use brdgme_cmd::requester::Requester;
+
+/// Answers `PlayerCounts`, fails `Status` requests according to their game and responds to
+/// everything else with the name of the request as a system error.
+struct Fixed;
+
+impl Requester for Fixed {
+    fn request(&mut self, req: &Request) -> Result<Response, Error> {
+        match *req {
+            Request::PlayerCounts => Ok(Response::PlayerCounts {
+                player_counts: vec![2, 3],
+            }),
+            Request::Status { ref game } if game == "user" => Ok(Response::user_error(
+                ErrorKind::NotYourTurn,
+                "not your turn",
+                Some(Span { start: 0, end: 4 }),
+            )),
+            Request::Status { .. } => Err(format_err!("requester failed")),
+            _ => Ok(Response::SystemError {
+                message: req.name().to_string(),
+            }),
+        }
+    }
+}
+
+fn handle(input: Value) -> Option<RpcResponse> {
+    jsonrpc::handle(&mut Fixed, input.to_string().as_bytes())
+}
+
+fn error_code(resp: &RpcResponse) -> i64 {
+    resp.error.as_ref().expect("expected an error").code
+}
+
+#[test]
+fn result() {
+    let resp = handle(json!({"jsonrpc": "2.0", "id": 1, "method": "PlayerCounts"})).unwrap();
+    assert_eq!(resp.id, json!(1));
+    assert!(resp.error.is_none());
+    assert_eq!(
+        serde_json::to_value(resp.result.unwrap()).unwrap(),
+        json!({"PlayerCounts": {"player_counts": [2, 3]}})
+    );
+}
+
+#[test]
+fn unit_variant_with_empty_params() {
+    for params in &[json!({}), json!([]), Value::Null] {
+        let resp = handle(json!({"jsonrpc": "2.0", "id": 1, "method": "Hello", "params": params}))
+            .unwrap();
+        assert_eq!(
+            error_code(&resp),
+            jsonrpc::SYSTEM_ERROR,
+            "params {}",
+            params
+        );
+        assert_eq!(resp.error.unwrap().message, "Hello");
+    }
+}
+
+#[test]
+fn struct_variant_with_empty_params() {
+    let resp =
+        handle(json!({"jsonrpc": "2.0", "id": 1, "method": "Status", "params": {}})).unwrap();
+    assert_eq!(error_code(&resp), jsonrpc::INVALID_PARAMS);
+}
+
+#[test]
+fn notification() {
+    assert!(handle(json!({"jsonrpc": "2.0", "method": "PlayerCounts"})).is_none());
+}
+
+#[test]
+fn null_id_is_not_a_notification() {
+    let resp = handle(json!({"jsonrpc": "2.0", "id": null, "method": "PlayerCounts"})).unwrap();
+    assert_eq!(resp.id, Value::Null);
+    assert!(resp.result.is_some());
+}
+
+#[test]
+fn method_not_found() {
+    let resp = handle(json!({"jsonrpc": "2.0", "id": "a", "method": "Nope"})).unwrap();
+    assert_eq!(resp.id, json!("a"));
+    assert_eq!(error_code(&resp), jsonrpc::METHOD_NOT_FOUND);
+}
+
+#[test]
+fn parse_error() {
+    let resp = jsonrpc::handle(&mut Fixed, b"{").unwrap();
+    assert_eq!(resp.id, Value::Null);
+    assert_eq!(error_code(&resp), jsonrpc::PARSE_ERROR);
+}
+
+#[test]
+fn invalid_version() {
+    let resp = handle(json!({"jsonrpc": "1.0", "id": 1, "method": "PlayerCounts"})).unwrap();
+    assert_eq!(error_code(&resp), jsonrpc::INVALID_REQUEST);
+}
+
+#[test]
+fn user_error() {
+    let resp = handle(json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "Status",
+        "params": {"game": "user"},
+    }))
+    .unwrap();
+    let error = resp.error.unwrap();
+    assert_eq!(error.code, jsonrpc::USER_ERROR);
+    assert_eq!(error.message, "not your turn");
+    assert_eq!(
+        error.data,
+        Some(json!({
+            "kind": ErrorKind::NotYourTurn,
+            "code": ErrorKind::NotYourTurn.code(),
+            "span": {"start": 0, "end": 4},
+        }))
+    );
+}
+
+#[test]
+fn system_error() {
+    let resp = handle(json!({"jsonrpc": "2.0", "id": 1, "method": "Info"})).unwrap();
+    assert_eq!(error_code(&resp), jsonrpc::SYSTEM_ERROR);
+}
+
+#[test]
+fn internal_error() {
+    let resp = handle(json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "Status",
+        "params": {"game": "fail"},
+    }))
+    .unwrap();
+    assert_eq!(error_code(&resp), jsonrpc::INTERNAL_ERROR);
+}
@@ -0,0 +1,103 @@
+use serde_json::{json, Value};
+
+use brdgme_cmd::api::{validate_options, GameOption, GameOptionKind};
+
+fn schema() -> Vec<GameOption> {
+    vec![
+        GameOption {
+            name: "fast".to_string(),
+            description: None,
+            kind: GameOptionKind::Bool { default: false },
+        },
+        GameOption {
+            name: "rounds".to_string(),
+            description: None,
+            kind: GameOptionKind::Int {
+                min: Some(1),
+                max: Some(10),
+                default: 3,
+            },
+        },
+        GameOption {
+            name: "board".to_string(),
+            description: None,
+            kind: GameOptionKind::Enum {
+                values: vec!["small".to_string(), "large".to_string()],
+                default: "small".to_string(),
+            },
+        },
+    ]
+}
+
+fn validate(options: Value) -> Result<Value, String> {
+    validate_options(&schema(), Some(&options))
+}
+
+#[test]
+fn fills_defaults() {
+    let defaults = json!({ "fast": false, "rounds": 3, "board": "small" });
+    assert_eq!(validate_options(&schema(), None), Ok(defaults.clone()));
+    assert_eq!(validate(Value::Null), Ok(defaults.clone()));
+    assert_eq!(validate(json!({})), Ok(defaults));
+    assert_eq!(
+        validate(json!({ "rounds": 5 })),
+        Ok(json!({ "fast": false, "rounds": 5, "board": "small" }))
+    );
+}
+
+#[test]
+fn rejects_non_objects() {
+    assert!(validate(json!([])).is_err());
+    assert!(validate(json!("fast")).is_err());
+}
+
+#[test]
+fn rejects_unknown_options() {
+    assert_eq!(
+        validate(json!({ "speed": 1 })),
+        Err("unknown option 'speed'".to_string())
+    );
+}
+
+#[test]
+fn rejects_wrong_types() {
+    assert_eq!(
+        validate(json!({ "fast": 1 })),
+        Err("option 'fast' must be a boolean".to_string())
+    );
+    assert_eq!(
+        validate(json!({ "rounds": "3" })),
+        Err("option 'rounds' must be an integer".to_string())
+    );
+    assert_eq!(
+        validate(json!({ "rounds": 2.5 })),
+        Err("option 'rounds' must be an integer".to_string())
+    );
+    assert_eq!(
+        validate(json!({ "board": true })),
+        Err("option 'board' must be a string".to_string())
+    );
+}
+
+#[test]
+fn enforces_int_bounds() {
+    assert_eq!(
+        validate(json!({ "rounds": 0 })),
+        Err("option 'rounds' must be at least 1".to_string())
+    );
+    assert_eq!(
+        validate(json!({ "rounds": 11 })),
+        Err("option 'rounds' must be at most 10".to_string())
+    );
+    assert!(validate(json!({ "rounds": 1 })).is_ok());
+    assert!(validate(json!({ "rounds": 10 })).is_ok());
+}
+
+#[test]
+fn enforces_enum_membership() {
+    assert_eq!(
+        validate(json!({ "board": "huge" })),
+        Err("option 'board' must be one of small, large".to_string())
+    );
+    assert!(validate(json!({ "board": "large" })).is_ok());
+}
@@ -0,0 +1,368 @@
+//! Serialised samples of every protocol type, shared by tests which check `schema::definitions`
+//! still matches the serde types.
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::json;
+
+use brdgme_game::command::Spec as CommandSpec;
+use brdgme_game::Status;
+
+use brdgme_cmd::api::{
+    CliLog, ErrorKind, GameOption, GameOptionKind, GameResponse, Hello, Info, PlayerRender,
+    PubRender, RenderFormat, ReplayCommand, Request, Response, Span,
+};
+use brdgme_cmd::bot_cli;
+use brdgme_cmd::schema;
+use brdgme_cmd::suggest::Suggestion;
+
+fn game() -> GameResponse {
+    GameResponse {
+        state: "{}".to_string(),
+        points: vec![1.5, 0.0],
+        status: Status::Active {
+            whose_turn: vec![0],
+            eliminated: vec![],
+        },
+        options: Some(json!({ "variant": "short" })),
+    }
+}
+
+fn finished_game() -> GameResponse {
+    GameResponse {
+        status: Status::Finished {
+            placings: vec![1, 2],
+            stats: vec![],
+        },
+        options: None,
+        ..game()
+    }
+}
+
+fn logs() -> Vec<CliLog> {
+    vec![CliLog {
+        content: "{{player 0}} played".to_string(),
+        at: "2018-01-02T03:04:05".parse::<NaiveDateTime>().unwrap(),
+        public: false,
+        to: vec![0],
+    }]
+}
+
+fn public_render() -> PubRender {
+    PubRender {
+        pub_state: "{}".to_string(),
+        render: "board".to_string(),
+    }
+}
+
+fn player_render() -> PlayerRender {
+    PlayerRender {
+        player_state: "{}".to_string(),
+        render: "hand".to_string(),
+        command_spec: Some(command_spec()),
+    }
+}
+
+/// A command spec using every variant.
+pub fn command_spec() -> CommandSpec {
+    CommandSpec::Doc {
+        name: "play".to_string(),
+        desc: Some("Play a card".to_string()),
+        spec: Box::new(CommandSpec::Chain(vec![
+            CommandSpec::Token("play".to_string()),
+            CommandSpec::Space,
+            CommandSpec::OneOf(vec![
+                CommandSpec::Enum {
+                    values: vec!["red".to_string(), "blue".to_string()],
+                    exact: false,
+                },
+                CommandSpec::Int {
+                    min: Some(1),
+                    max: None,
+                },
+            ]),
+            CommandSpec::Opt(Box::new(CommandSpec::Chain(vec![
+                CommandSpec::Space,
+                CommandSpec::Player,
+            ]))),
+            CommandSpec::Many {
+                spec: Box::new(CommandSpec::Token("again".to_string())),
+                min: Some(0),
+                max: None,
+                delim: ",".to_string(),
+            },
+        ])),
+    }
+}
+
+fn names() -> Vec<String> {
+    vec!["alice".to_string(), "bob".to_string()]
+}
+
+/// A sample of every request variant, with every render format.
+pub fn requests() -> Vec<Request> {
+    let mut requests = vec![
+        Request::Hello,
+        Request::PlayerCounts,
+        Request::Info,
+        Request::New {
+            players: 2,
+            seed: Some(5),
+            options: Some(json!({ "variant": "short" })),
+        },
+        Request::Options,
+        Request::Replay {
+            players: 2,
+            seed: None,
+            options: None,
+            names: names(),
+            commands: vec![ReplayCommand {
+                player: 0,
+                command: "play".to_string(),
+            }],
+        },
+        Request::Status {
+            game: "{}".to_string(),
+        },
+        Request::Play {
+            player: 0,
+            command: "play".to_string(),
+            names: names(),
+            game: "{}".to_string(),
+            diff: true,
+        },
+        Request::Validate {
+            player: 0,
+            command: "play".to_string(),
+            names: names(),
+            game: "{}".to_string(),
+        },
+        Request::Suggest {
+            player: 0,
+            partial_input: "pl".to_string(),
+            names: names(),
+            game: "{}".to_string(),
+        },
+        Request::PlayerRender {
+            player: 0,
+            game: "{}".to_string(),
+            format: RenderFormat::Markup,
+            width: Some(80),
+            names: names(),
+        },
+        Request::Logs {
+            logs: logs(),
+            player: Some(0),
+        },
+        Request::Migrate {
+            game: "{}".to_string(),
+            from_version: Some(1),
+        },
+        Request::Batch {
+            requests: vec![Request::PlayerCounts],
+            chain: true,
+        },
+    ];
+    for &format in &[
+        RenderFormat::Markup,
+        RenderFormat::Ansi,
+        RenderFormat::Plain,
+        RenderFormat::Html,
+        RenderFormat::Svg,
+    ] {
+        requests.push(Request::PubRender {
+            game: "{}".to_string(),
+            format,
+            width: None,
+            names: vec![],
+        });
+    }
+    requests
+}
+
+/// A sample of every response variant, with every error kind, option kind and game status.
+pub fn responses() -> Vec<Response> {
+    let mut responses = vec![
+        Response::Hello {
+            hello: Hello {
+                protocol_version: 1,
+                name: Some("game".to_string()),
+                version: None,
+                capabilities: vec!["Hello".to_string()],
+            },
+        },
+        Response::PlayerCounts {
+            player_counts: vec![2, 3],
+        },
+        Response::Info {
+            info: Info {
+                name: Some("game".to_string()),
+                description: None,
+                rules: Some("rules".to_string()),
+                player_counts: vec![2],
+                duration: Some(30),
+                version: None,
+            },
+        },
+        Response::Options {
+            options: vec![
+                GameOption {
+                    name: "fast".to_string(),
+                    description: Some("Fewer rounds".to_string()),
+                    kind: GameOptionKind::Bool { default: false },
+                },
+                GameOption {
+                    name: "rounds".to_string(),
+                    description: None,
+                    kind: GameOptionKind::Int {
+                        min: Some(-1),
+                        max: None,
+                        default: 3,
+                    },
+                },
+                GameOption {
+                    name: "variant".to_string(),
+                    description: None,
+                    kind: GameOptionKind::Enum {
+                        values: vec!["short".to_string()],
+                        default: "short".to_string(),
+                    },
+                },
+            ],
+        },
+        Response::New {
+            game: game(),
+            logs: logs(),
+            seed: Some(5),
+            public_render: public_render(),
+            player_renders: vec![player_render()],
+        },
+        Response::Status {
+            game: game(),
+            public_render: public_render(),
+            player_renders: vec![player_render()],
+        },
+        Response::Play {
+            game: game(),
+            logs: logs(),
+            can_undo: true,
+            remaining_input: String::new(),
+            public_render: public_render(),
+            player_renders: vec![player_render()],
+        },
+        Response::Replay {
+            game: game(),
+            logs: logs(),
+            command_logs: vec![logs()],
+            public_render: public_render(),
+            player_renders: vec![player_render()],
+        },
+        Response::Validate {
+            parsed: "play".to_string(),
+            remaining_input: String::new(),
+            logs: logs(),
+            can_undo: false,
+        },
+        Response::Suggest {
+            suggestions: vec![Suggestion {
+                token: "play".to_string(),
+                command: "play".to_string(),
+            }],
+            complete: false,
+        },
+        Response::ReplayError {
+            index: 0,
+            error: Box::new(Response::SystemError {
+                message: "failed".to_string(),
+            }),
+        },
+        Response::PubRender {
+            render: public_render(),
+        },
+        Response::PlayerRender {
+            render: PlayerRender {
+                command_spec: None,
+                ..player_render()
+            },
+        },
+        Response::Logs { logs: logs() },
+        Response::PlayDiff {
+            patch: json_patch::diff(
+                &json!({ "a": 1, "b": [1] }),
+                &json!({ "a": 2, "c": null }),
+            ),
+            logs: logs(),
+            can_undo: true,
+            remaining_input: String::new(),
+        },
+        Response::Migrate {
+            game: finished_game(),
+            public_render: public_render(),
+            player_renders: vec![],
+        },
+        Response::Batch {
+            responses: vec![Response::PlayerCounts {
+                player_counts: vec![2],
+            }],
+        },
+        Response::StateVersionError {
+            state_version: 1,
+            current_version: 2,
+            message: "old".to_string(),
+        },
+        Response::UserError {
+            message: "no".to_string(),
+            kind: None,
+            code: None,
+            span: None,
+        },
+        Response::SystemError {
+            message: "failed".to_string(),
+        },
+    ];
+    for &kind in &[
+        ErrorKind::PlayerCount,
+        ErrorKind::InvalidInput,
+        ErrorKind::NotYourTurn,
+        ErrorKind::Finished,
+        ErrorKind::Parse,
+        ErrorKind::InvalidOptions,
+        ErrorKind::UnexpectedInput,
+    ] {
+        responses.push(Response::user_error(kind, "no", Some(Span { start: 0, end: 2 })));
+    }
+    responses
+}
+
+pub fn bot_request() -> bot_cli::Request {
+    bot_cli::Request {
+        player: 0,
+        player_state: "{}".to_string(),
+        players: names(),
+        command_spec: command_spec(),
+        game_id: Some("game".to_string()),
+    }
+}
+
+pub fn bot_response() -> bot_cli::Response {
+    vec!["play".to_string()]
+}
+
+fn assert_valid<T: Serialize>(value: &T, name: &str) {
+    let value = serde_json::to_value(value).unwrap();
+    if let Err(e) = schema::validate(&value, name) {
+        panic!("{} doesn't match its definition at {}\n{}", name, e, value);
+    }
+}
+
+/// Panics if any sample doesn't match its type in `schema::definitions`.
+pub fn assert_all_valid() {
+    for req in requests() {
+        assert_valid(&req, "Request");
+    }
+    for resp in responses() {
+        assert_valid(&resp, "Response");
+    }
+    assert_valid(&bot_request(), "BotRequest");
+    assert_valid(&bot_response(), "BotResponse");
+}
@@ -0,0 +1,222 @@
+mod samples;
+
+use serde_json::Value;
+
+use std::collections::BTreeSet;
+
+use brdgme_game::command::Spec as CommandSpec;
+use brdgme_game::Status;
+
+use brdgme_cmd::api::{ErrorKind, GameOptionKind, RenderFormat, Request, Response};
+use brdgme_cmd::schema::{self, Kind};
+
+#[test]
+fn samples_match_definitions() {
+    samples::assert_all_valid();
+}
+
+/// Only compiles when every response variant is listed, so new variants get a sample.
+fn response_name(resp: &Response) -> &'static str {
+    match *resp {
+        Response::Hello { .. } => "Hello",
+        Response::PlayerCounts { .. } => "PlayerCounts",
+        Response::Info { .. } => "Info",
+        Response::Options { .. } => "Options",
+        Response::New { .. } => "New",
+        Response::Status { .. } => "Status",
+        Response::Play { .. } => "Play",
+        Response::Replay { .. } => "Replay",
+        Response::Validate { .. } => "Validate",
+        Response::Suggest { .. } => "Suggest",
+        Response::ReplayError { .. } => "ReplayError",
+        Response::PubRender { .. } => "PubRender",
+        Response::PlayerRender { .. } => "PlayerRender",
+        Response::Logs { .. } => "Logs",
+        Response::PlayDiff { .. } => "PlayDiff",
+        Response::Migrate { .. } => "Migrate",
+        Response::Batch { .. } => "Batch",
+        Response::StateVersionError { .. } => "StateVersionError",
+        Response::UserError { .. } => "UserError",
+        Response::SystemError { .. } => "SystemError",
+    }
+}
+
+/// Only compiles when every error kind is listed, so new kinds get a sample.
+fn error_kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::PlayerCount => "PlayerCount",
+        ErrorKind::InvalidInput => "InvalidInput",
+        ErrorKind::NotYourTurn => "NotYourTurn",
+        ErrorKind::Finished => "Finished",
+        ErrorKind::Parse => "Parse",
+        ErrorKind::InvalidOptions => "InvalidOptions",
+        ErrorKind::UnexpectedInput => "UnexpectedInput",
+    }
+}
+
+/// Only compiles when every render format is listed, so new formats get a sample.
+fn render_format_name(format: RenderFormat) -> &'static str {
+    match format {
+        RenderFormat::Markup => "Markup",
+        RenderFormat::Ansi => "Ansi",
+        RenderFormat::Plain => "Plain",
+        RenderFormat::Html => "Html",
+        RenderFormat::Svg => "Svg",
+    }
+}
+
+/// Only compiles when every option kind is listed, so new kinds get a sample.
+fn option_kind_name(kind: &GameOptionKind) -> &'static str {
+    match *kind {
+        GameOptionKind::Bool { .. } => "Bool",
+        GameOptionKind::Int { .. } => "Int",
+        GameOptionKind::Enum { .. } => "Enum",
+    }
+}
+
+/// Only compiles when every status is listed, so new statuses get a sample.
+fn status_name(status: &Status) -> &'static str {
+    match *status {
+        Status::Active { .. } => "Active",
+        Status::Finished { .. } => "Finished",
+    }
+}
+
+/// Adds the variant names used anywhere in `spec`, only compiles when every variant is listed so
+/// new variants get a sample.
+fn command_spec_names(spec: &CommandSpec, names: &mut BTreeSet<&'static str>) {
+    names.insert(match *spec {
+        CommandSpec::Token(_) => "Token",
+        CommandSpec::Enum { .. } => "Enum",
+        CommandSpec::Player => "Player",
+        CommandSpec::Int { .. } => "Int",
+        CommandSpec::Space => "Space",
+        CommandSpec::Chain(ref specs) => {
+            for s in specs {
+                command_spec_names(s, names);
+            }
+            "Chain"
+        }
+        CommandSpec::OneOf(ref specs) => {
+            for s in specs {
+                command_spec_names(s, names);
+            }
+            "OneOf"
+        }
+        CommandSpec::Opt(ref spec) => {
+            command_spec_names(spec, names);
+            "Opt"
+        }
+        CommandSpec::Many { ref spec, .. } => {
+            command_spec_names(spec, names);
+            "Many"
+        }
+        CommandSpec::Doc { ref spec, .. } => {
+            command_spec_names(spec, names);
+            "Doc"
+        }
+    });
+}
+
+/// The variant names of an enum from `schema::definitions`.
+fn variants(name: &str) -> BTreeSet<&'static str> {
+    match schema::definitions().into_iter().find(|d| d.name == name) {
+        Some(schema::Definition {
+            kind: Kind::Enum(variants),
+            ..
+        }) => variants.into_iter().map(|(name, _)| name).collect(),
+        _ => panic!("{} is not an enum", name),
+    }
+}
+
+#[test]
+fn requests_match_definitions() {
+    let requests = samples::requests();
+    let sampled: BTreeSet<&str> = requests.iter().map(Request::name).collect();
+    assert_eq!(
+        sampled,
+        Request::NAMES.iter().cloned().collect::<BTreeSet<&str>>()
+    );
+    assert_eq!(sampled, variants("Request"));
+    assert_eq!(
+        requests
+            .iter()
+            .filter_map(|req| match *req {
+                Request::PubRender { format, .. } => Some(render_format_name(format)),
+                _ => None,
+            }).collect::<BTreeSet<&str>>(),
+        variants("RenderFormat")
+    );
+}
+
+#[test]
+fn responses_match_definitions() {
+    let responses = samples::responses();
+    assert_eq!(
+        responses
+            .iter()
+            .map(response_name)
+            .collect::<BTreeSet<&str>>(),
+        variants("Response")
+    );
+    assert_eq!(
+        responses
+            .iter()
+            .filter_map(|resp| match *resp {
+                Response::UserError {
+                    kind: Some(kind), ..
+                } => Some(error_kind_name(kind)),
+                _ => None,
+            }).collect::<BTreeSet<&str>>(),
+        variants("ErrorKind")
+    );
+    assert_eq!(
+        responses
+            .iter()
+            .flat_map(|resp| match *resp {
+                Response::Options { ref options } => options.iter().collect(),
+                _ => vec![],
+            }).map(|o| option_kind_name(&o.kind))
+            .collect::<BTreeSet<&str>>(),
+        variants("GameOptionKind")
+    );
+    assert_eq!(
+        responses
+            .iter()
+            .filter_map(|resp| match *resp {
+                Response::New { ref game, .. }
+                | Response::Status { ref game, .. }
+                | Response::Play { ref game, .. }
+                | Response::Replay { ref game, .. }
+                | Response::Migrate { ref game, .. } => Some(status_name(&game.status)),
+                _ => None,
+            }).collect::<BTreeSet<&str>>(),
+        variants("Status")
+    );
+}
+
+#[test]
+fn command_specs_match_definitions() {
+    let mut names = BTreeSet::new();
+    command_spec_names(&samples::command_spec(), &mut names);
+    assert_eq!(names, variants("CommandSpec"));
+}
+
+#[test]
+fn validate_rejects_unknown_status_fields() {
+    let mut value = serde_json::to_value(Status::Active {
+        whose_turn: vec![0],
+        eliminated: vec![],
+    }).unwrap();
+    value["Active"]["extra"] = Value::Bool(true);
+    assert!(schema::validate(&value, "Status").is_err());
+}
+
+#[test]
+fn validate_rejects_unknown_fields() {
+    let mut value = serde_json::to_value(Request::Status {
+        game: "{}".to_string(),
+    }).unwrap();
+    value["Status"]["extra"] = Value::Bool(true);
+    assert!(schema::validate(&value, "Request").is_err());
+}
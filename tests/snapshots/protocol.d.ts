@@ -0,0 +1,164 @@
+// Generated by brdgme_cmd::typescript, do not edit.
+
+export type Request =
+  | "Hello"
+  | "PlayerCounts"
+  | "Info"
+  | { New: { players: number; seed?: number | null; options?: unknown | null } }
+  | "Options"
+  | { Replay: { players: number; seed?: number | null; options?: unknown | null; names: string[]; commands: ReplayCommand[] } }
+  | { Status: { game: string } }
+  | { Play: { player: number; command: string; names: string[]; game: string; diff?: boolean } }
+  | { Validate: { player: number; command: string; names: string[]; game: string } }
+  | { Suggest: { player: number; partial_input: string; names?: string[]; game: string } }
+  | { PubRender: { game: string; format?: RenderFormat; width?: number | null; names?: string[] } }
+  | { PlayerRender: { player: number; game: string; format?: RenderFormat; width?: number | null; names?: string[] } }
+  | { Logs: { logs: CliLog[]; player?: number | null } }
+  | { Migrate: { game: string; from_version?: number | null } }
+  | { Batch: { requests: Request[]; chain?: boolean } };
+
+export interface ReplayCommand {
+  player: number;
+  command: string;
+}
+
+export type RenderFormat =
+  | "Markup"
+  | "Ansi"
+  | "Plain"
+  | "Html"
+  | "Svg";
+
+/** `at` is a timestamp without a timezone, eg. 2018-01-02T03:04:05 */
+export interface CliLog {
+  content: string;
+  at: string;
+  public: boolean;
+  to: number[];
+}
+
+export interface GameResponse {
+  state: string;
+  points: number[];
+  status: Status;
+  options?: unknown | null;
+}
+
+export interface GameOption {
+  name: string;
+  description?: string | null;
+  kind: GameOptionKind;
+}
+
+export type GameOptionKind =
+  | { Bool: { default: boolean } }
+  | { Int: { min?: number | null; max?: number | null; default: number } }
+  | { Enum: { values: string[]; default: string } };
+
+export interface PubRender {
+  pub_state: string;
+  render: string;
+}
+
+export interface PlayerRender {
+  player_state: string;
+  render: string;
+  command_spec?: CommandSpec | null;
+}
+
+export interface Hello {
+  protocol_version: number;
+  name?: string | null;
+  version?: string | null;
+  capabilities: string[];
+}
+
+export interface Info {
+  name?: string | null;
+  description?: string | null;
+  rules?: string | null;
+  player_counts: number[];
+  duration?: number | null;
+  version?: string | null;
+}
+
+export type Response =
+  | { Hello: { hello: Hello } }
+  | { PlayerCounts: { player_counts: number[] } }
+  | { Info: { info: Info } }
+  | { Options: { options: GameOption[] } }
+  | { New: { game: GameResponse; logs: CliLog[]; seed?: number | null; public_render: PubRender; player_renders: PlayerRender[] } }
+  | { Status: { game: GameResponse; public_render: PubRender; player_renders: PlayerRender[] } }
+  | { Play: { game: GameResponse; logs: CliLog[]; can_undo: boolean; remaining_input: string; public_render: PubRender; player_renders: PlayerRender[] } }
+  | { Replay: { game: GameResponse; logs: CliLog[]; command_logs: CliLog[][]; public_render: PubRender; player_renders: PlayerRender[] } }
+  | { Validate: { parsed: string; remaining_input: string; logs: CliLog[]; can_undo: boolean } }
+  | { Suggest: { suggestions: Suggestion[]; complete: boolean } }
+  | { ReplayError: { index: number; error: Response } }
+  | { PubRender: { render: PubRender } }
+  | { PlayerRender: { render: PlayerRender } }
+  | { Logs: { logs: CliLog[] } }
+  | { PlayDiff: { patch: Patch; logs: CliLog[]; can_undo: boolean; remaining_input: string } }
+  | { Migrate: { game: GameResponse; public_render: PubRender; player_renders: PlayerRender[] } }
+  | { Batch: { responses: Response[] } }
+  | { StateVersionError: { state_version: number; current_version: number; message: string } }
+  | { UserError: { message: string; kind?: ErrorKind | null; code?: number | null; span?: Span | null } }
+  | { SystemError: { message: string } };
+
+export type ErrorKind =
+  | "PlayerCount"
+  | "InvalidInput"
+  | "NotYourTurn"
+  | "Finished"
+  | "Parse"
+  | "InvalidOptions"
+  | "UnexpectedInput";
+
+export interface Span {
+  start: number;
+  end: number;
+}
+
+export interface Suggestion {
+  token: string;
+  command: string;
+}
+
+/** The status of a game, defined by brdgme_game. */
+export type Status =
+  | { Active: { whose_turn: number[]; eliminated: number[] } }
+  | { Finished: { placings: number[]; stats: { [key: string]: unknown }[] } };
+
+/** A command spec defined by brdgme_game. */
+export type CommandSpec =
+  | { Token: string }
+  | { Enum: { values: string[]; exact: boolean } }
+  | "Player"
+  | { Int: { min?: number | null; max?: number | null } }
+  | "Space"
+  | { Chain: CommandSpec[] }
+  | { OneOf: CommandSpec[] }
+  | { Opt: CommandSpec }
+  | { Many: { spec: CommandSpec; min?: number | null; max?: number | null; delim: string } }
+  | { Doc: { name: string; desc?: string | null; spec: CommandSpec } };
+
+export type Patch = PatchOperation[];
+
+/** A JSON Patch (RFC 6902) operation. */
+export interface PatchOperation {
+  op: string;
+  path: string;
+  from?: string;
+  value?: unknown;
+}
+
+/** A request for `bot_cli::cli`. */
+export interface BotRequest {
+  player: number;
+  player_state: string;
+  players: string[];
+  command_spec: CommandSpec;
+  game_id?: string | null;
+}
+
+/** The commands a bot wants to play, from `bot_cli::cli`. */
+export type BotResponse = string[];
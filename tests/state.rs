@@ -0,0 +1,132 @@
+use serde_json::{json, Value};
+
+use std::env;
+use std::fs;
+use std::process;
+
+use brdgme_cmd::api::{StateEnvelope, StateFormat, StateKey};
+
+fn signed(key: &str) -> StateFormat {
+    StateFormat {
+        state_version: 2,
+        key: Some(StateKey::new(key).unwrap()),
+    }
+}
+
+fn game() -> Value {
+    json!({"board": [1, 2, 3], "turn": 0})
+}
+
+/// Replaces the signed inner state or signature with the result of `f`.
+fn tamper<F: FnOnce(&str) -> String>(state: &str, field: &str, f: F) -> String {
+    let mut signed: Value = serde_json::from_str(state).unwrap();
+    let tampered = f(signed[field].as_str().unwrap());
+    signed[field] = Value::String(tampered);
+    signed.to_string()
+}
+
+#[test]
+fn signed_round_trip() {
+    let format = signed("secret");
+    let state = format.encode(&game()).unwrap();
+    assert_eq!(
+        format.decode(&state).unwrap(),
+        StateEnvelope {
+            state_version: 2,
+            game: game(),
+        }
+    );
+}
+
+#[test]
+fn unsigned_round_trip() {
+    let format = StateFormat::default();
+    let state = format.encode(&game()).unwrap();
+    assert_eq!(format.decode(&state).unwrap().game, game());
+}
+
+#[test]
+fn modified_state_is_rejected() {
+    let format = signed("secret");
+    let state = tamper(&format.encode(&game()).unwrap(), "state", |s| {
+        s.replace("\"turn\":0", "\"turn\":1")
+    });
+    assert!(format.decode(&state).is_err());
+}
+
+#[test]
+fn modified_signature_is_rejected() {
+    let format = signed("secret");
+    let state = format.encode(&game()).unwrap();
+    let flipped = tamper(&state, "signature", |s| {
+        let last = if s.ends_with('0') { "1" } else { "0" };
+        format!("{}{}", &s[..s.len() - 1], last)
+    });
+    assert!(format.decode(&flipped).is_err());
+}
+
+#[test]
+fn malformed_signature_is_rejected() {
+    let format = signed("secret");
+    let state = format.encode(&game()).unwrap();
+    let odd = tamper(&state, "signature", |s| s[1..].to_string());
+    assert!(format.decode(&odd).is_err());
+    let non_hex = tamper(&state, "signature", |s| format!("zz{}", &s[2..]));
+    assert!(format.decode(&non_hex).is_err());
+    let non_ascii = tamper(&state, "signature", |s| format!("é{}", &s[2..]));
+    assert!(format.decode(&non_ascii).is_err());
+}
+
+#[test]
+fn unsigned_state_is_rejected() {
+    let state = StateFormat::default().encode(&game()).unwrap();
+    assert!(signed("secret").decode(&state).is_err());
+}
+
+#[test]
+fn wrong_key_is_rejected() {
+    let state = signed("secret").encode(&game()).unwrap();
+    assert!(signed("other").decode(&state).is_err());
+}
+
+#[test]
+fn empty_key_is_refused() {
+    assert!(StateKey::new("").is_err());
+}
+
+#[test]
+fn key_from_file_trims_trailing_whitespace() {
+    let path = env::temp_dir().join(format!("brdgme-state-key-{}", process::id()));
+    fs::write(&path, "secret \n\r\n").unwrap();
+    let key = StateKey::from_file(&path);
+    fs::write(&path, " \n").unwrap();
+    let blank = StateKey::from_file(&path);
+    fs::remove_file(&path).unwrap();
+
+    let state = signed("secret").encode(&game()).unwrap();
+    let format = StateFormat {
+        state_version: 2,
+        key: Some(key.unwrap()),
+    };
+    assert_eq!(format.decode(&state).unwrap().game, game());
+    assert!(blank.is_err());
+}
+
+#[test]
+fn legacy_state_is_version_zero() {
+    let legacy = game().to_string();
+    assert_eq!(
+        StateEnvelope::parse(&legacy).unwrap(),
+        StateEnvelope {
+            state_version: 0,
+            game: game(),
+        }
+    );
+    assert_eq!(
+        StateFormat::default()
+            .decode(&legacy)
+            .unwrap()
+            .state_version,
+        0
+    );
+}
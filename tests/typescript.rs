@@ -0,0 +1,25 @@
+mod samples;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Fails when the shape of the protocol changes, run with `UPDATE_SNAPSHOTS=1` to accept the new
+/// shape. The definitions are generated from `schema::definitions`, so the samples are checked
+/// against them first to catch changes to the serde types which weren't made to the definitions.
+#[test]
+fn typescript_snapshot() {
+    samples::assert_all_valid();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/protocol.d.ts");
+    let generated = brdgme_cmd::typescript::typescript();
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    let snapshot = fs::read_to_string(&path).unwrap();
+    assert!(
+        generated == snapshot,
+        "TypeScript definitions differ from {}, run with UPDATE_SNAPSHOTS=1 to update it",
+        path.display()
+    );
+}